@@ -0,0 +1,484 @@
+/*!
+This blueprint enables on-chain governance on top of the staking component. Staking IDs are used to vote on proposals.
+
+A proposal is open for a set voting period. Staking ID holders can vote for or against it, with a weight determined by the tokens staked to their ID at the end of the period before the proposal was created (preventing flash-stake voting):
+- Every stakable token has a voting weight per token, set by the owner. Tokens without a voting weight do not count.
+- Optionally, voting weight is scaled by the time the stake is still locked for (veToken-style), rewarding long-term commitment.
+- Staking IDs can delegate their voting power to other staking IDs. Delegated stake counts for the delegate as it was at the end of the period before the proposal was created, and does not count for the delegator if it was delegated at that time.
+- Stake delegated to an account does not count in this blueprint, as accounts cannot vote here. It only counts for governance systems that read the delegated weight of accounts from the staking component.
+
+When voting, the staked tokens of the staking ID are locked by the staking component until the end of the voting period, using the lock registry of the staking component (which needs to be dao_controlled). This prevents tokens from voting twice.
+Stake delegated to a voting staking ID is locked along with it: it cannot be unstaked, transferred or redelegated until the lock is released.
+The voting period can not exceed the maximum owner lock duration of the staking component. If the lock of a vote would extend the lock window of the staked tokens beyond that duration, it is capped at the end of the window instead, so the tokens can be unstaked before the voting period ends.
+As a new lock window can only start a day after the previous one ended, a vote fails if it is cast in that unlocked day.
+The action of a proposal is checked when the proposal is created, so an accepted proposal can always be finished.
+When the voting period has ended, anyone can finish the proposal. If quorum is reached and a majority voted for it, its action is executed by calling the owner methods of the staking component.
+*/
+
+use crate::staking::Staking;
+use crate::{Delegate, Id};
+use scrypto::prelude::*;
+
+// Proposal action enum, holding the owner method of the staking component that is called when a proposal is accepted.
+#[derive(ScryptoSbor, Clone)]
+pub enum ProposalAction {
+    SetPeriodInterval(i64),
+    SetMaxClaimDelay(i64),
+    SetUnstakeDelay(i64),
+    SetRewards(ResourceAddress, Decimal),
+}
+
+// Proposal status enum, holding the phase a proposal is in.
+#[derive(ScryptoSbor, Clone, PartialEq)]
+pub enum ProposalStatus {
+    Open,
+    Accepted,
+    Rejected,
+}
+
+// Proposal structure, holding all data about a proposal and the votes cast on it.
+#[derive(ScryptoSbor, Clone)]
+pub struct Proposal {
+    pub title: String,
+    pub description: String,
+    pub action: ProposalAction,
+    pub deadline: Instant,
+    pub snapshot_period: i64,
+    pub votes_for: Decimal,
+    pub votes_against: Decimal,
+    pub status: ProposalStatus,
+}
+
+#[blueprint]
+mod governance {
+    enable_method_auth! {
+        methods {
+            create_proposal => PUBLIC;
+            vote => PUBLIC;
+            finish_proposal => PUBLIC;
+            get_vote_weight => PUBLIC;
+            set_voting_period => restrict_to: [OWNER];
+            set_quorum => restrict_to: [OWNER];
+            set_proposal_threshold => restrict_to: [OWNER];
+            set_vote_weight => restrict_to: [OWNER];
+            set_ve_weighting => restrict_to: [OWNER];
+        }
+    }
+
+    struct Governance {
+        // the staking component this governance component controls
+        staking: Global<Staking>,
+        // vault holding the controller badge of the staking component, used to call its owner methods
+        staking_controller: Vault,
+        // resource manager of the staking IDs of the staking component
+        id_manager: ResourceManager,
+        // voting period of a proposal in days
+        voting_period: i64,
+        // minimum total vote weight for a proposal to be accepted
+        quorum: Decimal,
+        // minimum vote weight of a staking ID to create a proposal
+        proposal_threshold: Decimal,
+        // voting weight per staked token, only stakable tokens in this map count towards voting weight
+        vote_weights: HashMap<ResourceAddress, Decimal>,
+        // maximum lock duration in days for veToken-style weighting, if None, locking does not influence voting weight
+        ve_max_lock: Option<i64>,
+        // counter for the proposals
+        proposal_counter: u64,
+        // keyvaluestore, holding all proposals
+        proposals: KeyValueStore<u64, Proposal>,
+        // keyvaluestore, holding which staking IDs have voted on which proposals
+        votes: KeyValueStore<(u64, NonFungibleLocalId), bool>,
+    }
+
+    impl Governance {
+        // this function instantiates the governance component
+        //
+        // ## INPUT
+        // - `controller`: the address of the controller badge, which will be the owner of the governance component
+        // - `staking`: the staking component to govern, which needs to be dao_controlled
+        // - `staking_controller`: the controller badge of the staking component
+        // - `id_address`: the address of the staking IDs of the staking component
+        // - `voting_period`: the voting period of a proposal in days
+        // - `quorum`: the minimum total vote weight for a proposal to be accepted
+        // - `proposal_threshold`: the minimum vote weight of a staking ID to create a proposal, needs to be positive to prevent proposal spam
+        // - `vote_weights`: the voting weight per staked token for every stakable token that counts towards voting weight
+        //
+        // ## OUTPUT
+        // - the governance component
+        //
+        // ## LOGIC
+        // - the method checks whether the proposal threshold is positive
+        // - the method checks whether the voting period fits in the maximum owner lock duration of the staking component, so votes can lock stake until the deadline
+        // - the controller badge of the staking component is stored
+        // - the governance component is instantiated
+        pub fn new(
+            controller: ResourceAddress,
+            staking: Global<Staking>,
+            staking_controller: Bucket,
+            id_address: ResourceAddress,
+            voting_period: i64,
+            quorum: Decimal,
+            proposal_threshold: Decimal,
+            vote_weights: HashMap<ResourceAddress, Decimal>,
+        ) -> Global<Governance> {
+            assert!(proposal_threshold > dec!(0), "Proposal threshold needs to be positive.");
+            assert!(
+                voting_period <= staking.get_max_owner_lock_duration(),
+                "Voting period exceeds the maximum owner lock duration of the staking component."
+            );
+
+            Self {
+                staking,
+                staking_controller: Vault::with_bucket(staking_controller),
+                id_manager: ResourceManager::from(id_address),
+                voting_period,
+                quorum,
+                proposal_threshold,
+                vote_weights,
+                ve_max_lock: None,
+                proposal_counter: 0,
+                proposals: KeyValueStore::new(),
+                votes: KeyValueStore::new(),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::Fixed(rule!(require(controller))))
+            .globalize()
+        }
+
+        // This method creates a new proposal
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID of the proposer
+        // - `title`: the title of the proposal
+        // - `description`: the description of the proposal
+        // - `action`: the action executed when the proposal is accepted
+        //
+        // ## OUTPUT
+        // - the ID of the proposal
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks whether the action can be executed by the staking component
+        // - the method checks whether the staking ID has enough vote weight to create a proposal
+        // - the method stores the proposal, open for voting until the end of the voting period, with the last finished period as snapshot
+        pub fn create_proposal(
+            &mut self,
+            id_proof: NonFungibleProof,
+            title: String,
+            description: String,
+            action: ProposalAction,
+        ) -> u64 {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            self.check_action(&action);
+
+            let snapshot_period = self.staking.get_current_period() - 1;
+
+            assert!(
+                self.get_vote_weight(id.clone(), snapshot_period)
+                    + self.get_delegated_vote_weight(id, snapshot_period)
+                    >= self.proposal_threshold,
+                "Not enough vote weight to create a proposal."
+            );
+
+            self.proposal_counter += 1;
+            self.proposals.insert(
+                self.proposal_counter,
+                Proposal {
+                    title,
+                    description,
+                    action,
+                    deadline: Clock::current_time_rounded_to_minutes()
+                        .add_days(self.voting_period)
+                        .unwrap(),
+                    snapshot_period,
+                    votes_for: dec!(0),
+                    votes_against: dec!(0),
+                    status: ProposalStatus::Open,
+                },
+            );
+
+            self.proposal_counter
+        }
+
+        // This method casts a vote on a proposal
+        //
+        // ## INPUT
+        // - `proposal_id`: the ID of the proposal
+        // - `id_proof`: the proof of the staking ID
+        // - `support`: whether to vote for or against the proposal
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks whether the proposal is open and the staking ID has not voted yet
+        // - the method calculates the vote weight of the staking ID at the proposal snapshot, including stake delegated to it
+        // - the method locks all own stake and stake delegated to the staking ID counting towards the vote weight until the end of the voting period, capped at the end of its lock window
+        // - the vote fails if the stake is in the unlocked day after a lock window
+        // - the method records the vote
+        pub fn vote(&mut self, proposal_id: u64, id_proof: NonFungibleProof, support: bool) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            let mut proposal = self
+                .proposals
+                .get(&proposal_id)
+                .expect("Proposal not found.")
+                .clone();
+
+            assert!(proposal.status == ProposalStatus::Open, "Proposal is not open.");
+            assert!(
+                !Clock::current_time_is_at_or_after(proposal.deadline, TimePrecision::Minute),
+                "Voting period has ended."
+            );
+            assert!(
+                self.votes.get(&(proposal_id, id.clone())).is_none(),
+                "Staking ID has already voted on this proposal."
+            );
+
+            let vote_weight = self.get_vote_weight(id.clone(), proposal.snapshot_period)
+                + self.get_delegated_vote_weight(id.clone(), proposal.snapshot_period);
+            assert!(vote_weight > dec!(0), "No vote weight available.");
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let current_period = self.staking.get_current_period();
+            for address in self.vote_weights.keys() {
+                let own_stake = id_data
+                    .resources
+                    .get(address)
+                    .filter(|_| !id_data.delegations.contains_key(address))
+                    .map_or(dec!(0), |resource| resource.amount_staked);
+                let delegated_stake = self
+                    .staking
+                    .get_delegated_weight(Delegate::Id(id.clone()), *address, current_period);
+
+                if own_stake > dec!(0) || delegated_stake > dec!(0) {
+                    self.staking_controller.authorize_with_all(|| {
+                        self.staking
+                            .set_lock(*address, proposal.deadline, id.clone(), proposal_id)
+                    });
+                }
+            }
+
+            if support {
+                proposal.votes_for += vote_weight;
+            } else {
+                proposal.votes_against += vote_weight;
+            }
+
+            self.proposals.insert(proposal_id, proposal);
+            self.votes.insert((proposal_id, id), support);
+        }
+
+        // This method finishes a proposal after its voting period has ended
+        //
+        // ## INPUT
+        // - `proposal_id`: the ID of the proposal
+        //
+        // ## OUTPUT
+        // - the status of the proposal
+        //
+        // ## LOGIC
+        // - the method checks whether the voting period has ended
+        // - the method checks whether quorum is reached and a majority voted for the proposal
+        // - the method closes the proposal in the staking component, releasing all locks set for it
+        // - if accepted, the method executes the proposal action
+        pub fn finish_proposal(&mut self, proposal_id: u64) -> ProposalStatus {
+            let mut proposal = self
+                .proposals
+                .get(&proposal_id)
+                .expect("Proposal not found.")
+                .clone();
+
+            assert!(proposal.status == ProposalStatus::Open, "Proposal is already finished.");
+            assert!(
+                Clock::current_time_is_at_or_after(proposal.deadline, TimePrecision::Minute),
+                "Voting period has not ended yet."
+            );
+
+            if proposal.votes_for + proposal.votes_against >= self.quorum
+                && proposal.votes_for > proposal.votes_against
+            {
+                proposal.status = ProposalStatus::Accepted;
+            } else {
+                proposal.status = ProposalStatus::Rejected;
+            }
+
+            self.staking_controller
+                .authorize_with_all(|| self.staking.close_proposal(proposal_id));
+
+            if proposal.status == ProposalStatus::Accepted {
+                self.execute(proposal.action.clone());
+            }
+
+            let status = proposal.status.clone();
+            self.proposals.insert(proposal_id, proposal);
+
+            status
+        }
+
+        // This method calculates the vote weight of a staking ID at a period, excluding stake delegated to it
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `period`: the snapshot period
+        //
+        // ## OUTPUT
+        // - the vote weight
+        //
+        // ## LOGIC
+        // - the method iterates over all stakable tokens with a voting weight
+        // - every token staked to the staking ID at the snapshot period that was not delegated at the snapshot period counts with its voting weight
+        // - if veToken-style weighting is enabled, the weight is scaled by the remaining lock time, relative to the maximum lock duration
+        pub fn get_vote_weight(&self, id: NonFungibleLocalId, period: i64) -> Decimal {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let now = Clock::current_time_rounded_to_minutes();
+            let mut vote_weight: Decimal = dec!(0);
+
+            for (address, weight) in self.vote_weights.iter() {
+                let own_stake = self.staking.get_stake_at(id.clone(), *address, period)
+                    - self.staking.get_delegated_stake_at(id.clone(), *address, period);
+                if own_stake <= dec!(0) {
+                    continue;
+                }
+
+                let mut resource_weight = own_stake * *weight;
+
+                if let Some(ve_max_lock) = self.ve_max_lock {
+                    let remaining_lock: i64 = id_data
+                        .resources
+                        .get(address)
+                        .and_then(|resource| resource.locked_until)
+                        .map_or(0, |locked_until| {
+                            (locked_until.seconds_since_unix_epoch - now.seconds_since_unix_epoch).max(0)
+                        });
+                    let max_lock: i64 = ve_max_lock * 86400;
+                    resource_weight = resource_weight * Decimal::from(remaining_lock.min(max_lock))
+                        / Decimal::from(max_lock);
+                }
+
+                vote_weight += resource_weight;
+            }
+
+            vote_weight
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////ADMIN METHODS/////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        pub fn set_voting_period(&mut self, new_period: i64) {
+            assert!(
+                new_period <= self.staking.get_max_owner_lock_duration(),
+                "Voting period exceeds the maximum owner lock duration of the staking component."
+            );
+            self.voting_period = new_period;
+        }
+
+        pub fn set_quorum(&mut self, new_quorum: Decimal) {
+            self.quorum = new_quorum;
+        }
+
+        pub fn set_proposal_threshold(&mut self, new_threshold: Decimal) {
+            assert!(new_threshold > dec!(0), "Proposal threshold needs to be positive.");
+            self.proposal_threshold = new_threshold;
+        }
+
+        pub fn set_vote_weight(&mut self, address: ResourceAddress, weight: Decimal) {
+            self.vote_weights.insert(address, weight);
+        }
+
+        pub fn set_ve_weighting(&mut self, max_lock: Option<i64>) {
+            assert!(max_lock.map_or(true, |max_lock| max_lock > 0), "Maximum lock duration must be positive.");
+            self.ve_max_lock = max_lock;
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////HELPER METHODS////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        /// This method calculates the vote weight delegated to a staking ID.
+        ///
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `period`: the snapshot period
+        ///
+        /// ## OUTPUT
+        /// - the delegated vote weight
+        ///
+        /// ## LOGIC
+        /// - the method reads the stake delegated to the staking ID at the snapshot period for every stakable token with a voting weight
+        /// - every delegated token counts with its voting weight
+
+        fn get_delegated_vote_weight(&self, id: NonFungibleLocalId, period: i64) -> Decimal {
+            let mut vote_weight: Decimal = dec!(0);
+
+            for (address, weight) in self.vote_weights.iter() {
+                vote_weight += self
+                    .staking
+                    .get_delegated_weight(Delegate::Id(id.clone()), *address, period)
+                    * *weight;
+            }
+
+            vote_weight
+        }
+
+        /// This method checks whether a proposal action can be executed by the staking component.
+        ///
+        /// ## INPUT
+        /// - `action`: the proposal action
+        ///
+        /// ## OUTPUT
+        /// - none
+        ///
+        /// ## LOGIC
+        /// - the method checks the arguments of the action against the constraints of the matching owner method
+        /// - stakables are never removed and the maximum unstaking delay is fixed, so a checked action stays executable
+
+        fn check_action(&self, action: &ProposalAction) {
+            match action {
+                ProposalAction::SetPeriodInterval(interval) => {
+                    assert!(*interval > 0, "Period interval needs to be positive.");
+                }
+                ProposalAction::SetMaxClaimDelay(delay) => {
+                    assert!(*delay >= 0, "Maximum claim delay cannot be negative.");
+                }
+                ProposalAction::SetUnstakeDelay(delay) => {
+                    assert!(
+                        *delay >= 0 && *delay <= self.staking.get_max_unstaking_delay(),
+                        "Unstaking delay needs to be between 0 and the maximum unstaking delay."
+                    );
+                }
+                ProposalAction::SetRewards(address, reward) => {
+                    assert!(self.staking.is_stakable(*address), "Stakable not found.");
+                    assert!(*reward >= dec!(0), "Reward amount cannot be negative.");
+                }
+            }
+        }
+
+        /// This method executes an accepted proposal action on the staking component.
+        ///
+        /// ## INPUT
+        /// - `action`: the proposal action
+        ///
+        /// ## OUTPUT
+        /// - none
+        ///
+        /// ## LOGIC
+        /// - the method calls the matching owner method of the staking component, authorized by the controller badge
+
+        fn execute(&self, action: ProposalAction) {
+            self.staking_controller.authorize_with_all(|| match action {
+                ProposalAction::SetPeriodInterval(interval) => self.staking.set_period_interval(interval),
+                ProposalAction::SetMaxClaimDelay(delay) => self.staking.set_max_claim_delay(delay),
+                ProposalAction::SetUnstakeDelay(delay) => self.staking.set_unstake_delay(delay),
+                ProposalAction::SetRewards(address, reward) => self.staking.set_rewards(address, reward),
+            });
+        }
+    }
+}
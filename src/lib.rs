@@ -15,13 +15,20 @@ This NFT staking ID approach has some disadvantages over simple OneResourcePool
 - Wallet display of staked tokens is more difficult, as staked amounts are stored by an NFT (staking ID). Ideally, users need to use some kind of front-end to see their staked tokens.
 - Staking rewards are distributed periodically, not continuously.
 - User needs to claim rewards manually. Though this could be automated in some way.
-- Staked tokens are not liquid, making it impossible to use them in traditional DEXes. Though they are transferable to other user's staking IDs, so a DEX could be built on top of this system. This way, liquidity could be provided while still earning staking fees.
+- Staked tokens are not liquid, making it impossible to use them in traditional DEXes. Though they are transferable to other user's staking IDs, so a DEX can be built on top of this system (the order book blueprint in this package is an example). This way, liquidity could be provided while still earning staking fees.
 - It is more complex to set up and manage.
 */
 
 use scrypto::prelude::*;
 
+pub mod governance;
+pub mod oracle;
+pub mod order_book;
+
+use oracle::PriceOracle;
+
 // NFT receipt structure, minted when an unstake is requested, redeemable after a set delay.
+// For NFT stakables, the receipt holds the unstaked NFTs, which are returned when it is redeemed.
 #[derive(ScryptoSbor, NonFungibleData)]
 pub struct UnstakeReceipt {
     #[mutable]
@@ -30,15 +37,63 @@ pub struct UnstakeReceipt {
     pub amount: Decimal,
     #[mutable]
     pub redemption_time: Instant,
+    #[mutable]
+    pub nfts: Vec<NonFungibleLocalId>,
 }
 
 // Staking ID structure, holding staked and locked amounts and date until which they are locked. Also stores the next period to claim rewards (updated after a user has claimed them).
+// Voting power of a stakable token can be delegated to another staking ID or account, while rewards stay with the staking ID.
+// Boost NFTs can be bonded to the staking ID, multiplying its weight in the reward distribution by the boost multiplier.
+// A staking ID can be referred by another staking ID, which then earns referral rewards when this staking ID claims its rewards.
+// If a payout destination is set, anyone can claim the rewards of the staking ID to that destination.
+// The name, description and key image are refreshed by the component on every change, so wallets can display a summary of the staking ID.
 #[derive(ScryptoSbor, NonFungibleData)]
 pub struct Id {
+    #[mutable]
+    pub name: String,
+    #[mutable]
+    pub description: String,
+    #[mutable]
+    pub key_image_url: Url,
     #[mutable]
     pub resources: HashMap<ResourceAddress, Resource>,
     #[mutable]
     pub next_period: i64,
+    #[mutable]
+    pub delegations: HashMap<ResourceAddress, Delegate>,
+    #[mutable]
+    pub boost_nfts: Vec<NonFungibleLocalId>,
+    #[mutable]
+    pub boost_multiplier: Decimal,
+    pub referrer: Option<NonFungibleLocalId>,
+    #[mutable]
+    pub referral_rewards: Decimal,
+    #[mutable]
+    pub payout_destination: Option<PayoutDestination>,
+}
+
+// ID image enum, holding how the key image of staking IDs is determined.
+// - Template: a URL in which {id}, {tier} and {stake} are replaced by the staking ID's number, tier and total stake.
+// - Svg: an SVG image rendered on-ledger from the staking ID's stake summary, as a data URI.
+#[derive(ScryptoSbor, Clone)]
+pub enum IdImage {
+    Template(String),
+    Svg,
+}
+
+// Payout destination enum, holding where rewards claimed through claim_for are deposited.
+// A component is called with the given method name, which needs to accept a single bucket.
+#[derive(ScryptoSbor, Clone)]
+pub enum PayoutDestination {
+    Account(ComponentAddress),
+    Component(ComponentAddress, String),
+}
+
+// Delegate enum, holding the staking ID or account voting power is delegated to.
+#[derive(ScryptoSbor, Clone, PartialEq, Eq, Hash)]
+pub enum Delegate {
+    Id(NonFungibleLocalId),
+    Account(ComponentAddress),
 }
 
 // Lock structure, holding the information about locking options of a token.
@@ -48,13 +103,146 @@ pub struct Lock {
     pub duration: i64,
 }
 
+// DAO lock structure, holding the locks set by the DAO on a stakable token of a staking ID, keyed by proposal ID.
+// Overlapping locks form a lock window, which cannot end later than the maximum owner lock duration after its start, whichever proposal extends it.
+// A new window can only start a day after the previous one ended, so the staker always gets a chance to unstake.
+#[derive(ScryptoSbor, Clone)]
+pub struct DaoLock {
+    pub window_start: Instant,
+    pub window_end: Instant,
+    pub proposals: HashMap<u64, Instant>,
+}
+
+// Stake limits structure, holding the minimum amount per stake, and optional caps on the total amount staked and the amount staked per staking ID.
+#[derive(ScryptoSbor, Clone)]
+pub struct StakeLimits {
+    pub min_stake: Decimal,
+    pub max_total_stake: Option<Decimal>,
+    pub max_stake_per_id: Option<Decimal>,
+}
+
+// Stake access enum, holding the rule that decides who is allowed to stake a stakable token.
+// An allowlist is a Merkle root of allowed accounts, of which the leaves are the hashes of the account address bytes.
+#[derive(ScryptoSbor, Clone)]
+pub enum StakeAccess {
+    Public,
+    Badge(ResourceAddress),
+    Allowlist(Hash),
+}
+
+// Access proof enum, holding the proof a staker supplies to pass the stake access rule of a stakable token.
+// For an allowlist, this is the account and the Merkle proof (sibling hashes from leaf to root) of its inclusion.
+#[derive(ScryptoSbor)]
+pub enum AccessProof {
+    Badge(Proof),
+    Allowlist(ComponentAddress, Vec<Hash>),
+}
+
+// Boost table enum, holding how the boost multiplier of a staking ID is determined from its bonded boost NFTs.
+// - Count: a list of (minimum amount of NFTs, multiplier), of which the highest reached multiplier applies.
+// - PerNft: a multiplier per NFT (e.g. derived from its rarity), of which the highest of all bonded NFTs applies.
+#[derive(ScryptoSbor, Clone)]
+pub enum BoostTable {
+    Count(Vec<(u64, Decimal)>),
+    PerNft(HashMap<NonFungibleLocalId, Decimal>),
+}
+
+// Boost structure, holding the boost NFT collection, its multiplier table and the vault holding all bonded boost NFTs.
+#[derive(ScryptoSbor)]
+pub struct Boost {
+    pub address: ResourceAddress,
+    pub table: BoostTable,
+    pub vault: NonFungibleVault,
+}
+
+// Resource structure, holding the staked amount of a token on a staking ID, and the date until which it is locked.
+// Newly staked tokens of a stakable with a warm-up are pending, as (activation period, amount), and only earn rewards from their activation period on.
+// For NFT stakables, the staked NFTs are held with their weight, and the staked amount is the total weight of these NFTs.
+// The period the current stake started is recorded to determine the loyalty multiplier of every period, which is applied to the weight of the stake.
 #[derive(ScryptoSbor, Clone)]
 pub struct Resource {
     pub amount_staked: Decimal,
     pub locked_until: Option<Instant>,
+    pub pending_stake: Vec<(i64, Decimal)>,
+    pub nfts: HashMap<NonFungibleLocalId, Decimal>,
+    pub stake_start: i64,
+}
+
+// Target APR structure, holding the yearly rate a stakable aims to pay, and the price of a staked token in reward tokens supplied by the owner.
+// The reward per period is capped per period and by a budget, which decreases with every period's emission.
+#[derive(ScryptoSbor, Clone)]
+pub struct TargetApr {
+    pub apr: Decimal,
+    pub price_ratio: Decimal,
+    pub max_per_period: Decimal,
+    pub budget: Decimal,
+}
+
+// Pool unit enum, tagging a stakable as the pool unit of a Radix native pool, holding the pool and the underlying token the stakable is valued in.
+#[derive(ScryptoSbor, Clone)]
+pub enum PoolUnit {
+    One(ComponentAddress, ResourceAddress),
+    Two(ComponentAddress, ResourceAddress),
+    Multi(ComponentAddress, ResourceAddress),
+}
+
+// NFT weight enum, holding how the weight of an NFT of an NFT stakable is determined.
+// Either by a table of weights per NFT (for instance based on rarity) with a default weight for NFTs not in it, or by a numeric field of the NFT data, by its index.
+#[derive(ScryptoSbor, Clone)]
+pub enum NftWeight {
+    Table(HashMap<NonFungibleLocalId, Decimal>, Decimal),
+    Field(u32),
+}
+
+// Loyalty structure, holding the multiplier schedule of a stakable for staking continuously, as (minimum periods staked, multiplier), capped by a maximum multiplier.
+// On unstaking, the staking duration is either reset, or decayed proportionally to the share of the stake that is unstaked.
+// The weight changes of every step of the schedule are scheduled when a staking ID stakes or unstakes, so the multiplier applies from the period it is reached in.
+// The schedule can only be changed while nothing is staked, as the scheduled weight changes depend on it.
+#[derive(ScryptoSbor, Clone)]
+pub struct Loyalty {
+    pub schedule: Vec<(i64, Decimal)>,
+    pub cap: Decimal,
+    pub decay: bool,
+}
+
+// Checkpoints structure, holding the history of an amount as (period, amount), stored in keyvaluestores so a long history is not loaded on every call.
+// The checkpointed periods are indexed in the order they are written, so the amount at a past period can be found by binary search.
+#[derive(ScryptoSbor)]
+pub struct Checkpoints {
+    pub count: u64,
+    pub periods: KeyValueStore<u64, i64>,
+    pub amounts: KeyValueStore<i64, Decimal>,
+}
+
+impl Checkpoints {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            periods: KeyValueStore::new(),
+            amounts: KeyValueStore::new(),
+        }
+    }
+}
+
+impl Default for Checkpoints {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Stakable unit structure, used by the component to data about a stakable token.
+// Total amount staked is also recorded as checkpoints of (period, amount), to be able to look up the total stake at a past period.
+// The weight is the total amount staked that earns rewards, multiplied by the boost multipliers of the staking IDs. Pending weight is added to it at the start of its activation period.
+// Optionally, a non-transferable stake token is minted to stakers, of which the supply always equals the amount staked, so wallets can display staked amounts.
+// If revenue tokens are set, the revenue deposited in those tokens during a period is distributed instead of rewards when the period ends, recorded as revenue per unit of weight per token.
+// The revenue of a period that is not claimed yet is recorded as well, so the owner can sweep it once the claim delay has passed.
+// Funded reward streams are scheduled as (period, amount), distributed on top of the reward amount in their period.
+// If a target APR is set, the reward amount is calculated from the active amount staked (excluding stake that is still warming up, before multipliers) every period instead.
+// If the stakable is a pool unit, it is valued by the redeemable amount of its underlying token, instead of its amount staked.
+// If the stakable is an NFT collection, every staked NFT counts as its weight, determined when it is staked.
+// If the stakable has a loyalty schedule, the weight of a staking ID is multiplied by the loyalty multiplier it reaches in every period.
+// If the stakable is a basket, it is staked in basket units, each requiring a fixed amount of every leg token, which are held in the vaults of the legs.
+// Leg tokens held for baskets count towards the stake limits of the leg, and staking a basket requires access to every leg.
 #[derive(ScryptoSbor)]
 pub struct StakableUnit {
     pub address: ResourceAddress,
@@ -62,7 +250,26 @@ pub struct StakableUnit {
     pub vault: Vault,
     pub reward_amount: Decimal,
     pub lock: Lock,
+    pub limits: StakeLimits,
+    pub access: StakeAccess,
     pub rewards: KeyValueStore<i64, Decimal>,
+    pub checkpoints: Checkpoints,
+    pub warmup_periods: i64,
+    pub weight: Decimal,
+    pub pending_weight: KeyValueStore<i64, Decimal>,
+    pub active_amount: Decimal,
+    pub pending_amount: KeyValueStore<i64, Decimal>,
+    pub stake_token: Option<ResourceManager>,
+    pub revenue_tokens: Option<Vec<ResourceAddress>>,
+    pub pending_revenue: HashMap<ResourceAddress, Decimal>,
+    pub revenue: KeyValueStore<i64, HashMap<ResourceAddress, Decimal>>,
+    pub unclaimed_revenue: KeyValueStore<i64, HashMap<ResourceAddress, Decimal>>,
+    pub stream: Vec<(i64, Decimal)>,
+    pub target_apr: Option<TargetApr>,
+    pub pool_unit: Option<PoolUnit>,
+    pub nft_weight: Option<NftWeight>,
+    pub basket: Option<HashMap<ResourceAddress, Decimal>>,
+    pub loyalty: Option<Loyalty>,
 }
 
 // Stake transfer receipt structure, minted when a user wants to transfer their staked tokens, redeemable by other users to add these tokens to their own staking ID.
@@ -72,31 +279,234 @@ pub struct StakeTransferReceipt {
     pub amount: Decimal,
 }
 
+// Gauge structure, holding a global emission per period that is split across stakables by their gauge weights, instead of their own reward amounts.
+// If a vote resource is set, staking IDs can allocate their weight in that stakable to gauges each period, and the votes of a period replace the owner-set weights for that period.
+// If value weighted, the weight of every stakable is multiplied by the value of its staked tokens, using the prices of the price oracle.
+#[derive(ScryptoSbor, Clone)]
+pub struct Gauge {
+    pub emission: Decimal,
+    pub weights: HashMap<ResourceAddress, Decimal>,
+    pub vote_resource: Option<ResourceAddress>,
+    pub value_weighted: bool,
+}
+
+// Bribe structure, holding tokens deposited by anyone for the staking IDs that voted for a stakable in a gauge period, claimable pro-rata to their votes.
+// After the claim delay has passed, unclaimed tokens are returned to the refund account, or roll forward to the current period if no refund account is set.
+#[derive(ScryptoSbor)]
+pub struct Bribe {
+    pub address: ResourceAddress,
+    pub period: i64,
+    pub amount: Decimal,
+    pub vault: FungibleVault,
+    pub refund_account: Option<ComponentAddress>,
+}
+
+// Pause structure, holding which user facing methods are currently paused by the guardian.
+#[derive(ScryptoSbor, Clone, Default)]
+pub struct Pause {
+    pub stake: bool,
+    pub unstake: bool,
+    pub claim: bool,
+    pub lock: bool,
+    pub vote: bool,
+}
+
+// Records an amount for a period in a list of checkpoints, overwriting the checkpoint of that period if it exists. Periods are only ever written in increasing order.
+fn write_checkpoint(checkpoints: &mut Checkpoints, period: i64, amount: Decimal) {
+    if checkpoints.amounts.get(&period).is_none() {
+        checkpoints.periods.insert(checkpoints.count, period);
+        checkpoints.count += 1;
+    }
+    checkpoints.amounts.insert(period, amount);
+}
+
+// Looks up the amount at a period in a list of checkpoints, which is the amount of the latest checkpoint at or before that period, found by binary search over the checkpointed periods.
+fn read_checkpoint(checkpoints: &Checkpoints, period: i64) -> Decimal {
+    let mut low: u64 = 0;
+    let mut high: u64 = checkpoints.count;
+    while low < high {
+        let middle = (low + high) / 2;
+        if *checkpoints.periods.get(&middle).unwrap() <= period {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    if low == 0 {
+        return dec!(0);
+    }
+    let checkpoint_period = *checkpoints.periods.get(&(low - 1)).unwrap();
+    checkpoints.amounts.get(&checkpoint_period).map_or(dec!(0), |amount| *amount)
+}
+
+// Looks up the amount of the latest checkpoint in a list of checkpoints.
+fn latest_checkpoint(checkpoints: &Checkpoints) -> Decimal {
+    if checkpoints.count == 0 {
+        return dec!(0);
+    }
+    let checkpoint_period = *checkpoints.periods.get(&(checkpoints.count - 1)).unwrap();
+    checkpoints.amounts.get(&checkpoint_period).map_or(dec!(0), |amount| *amount)
+}
+
+// Calculates the value of an amount of tokens staked to a stakable, as (priced token, amount), which for pool units is the redeemable amount of the underlying token.
+fn staked_value(stakable: &StakableUnit, amount: Decimal) -> (ResourceAddress, Decimal) {
+    match &stakable.pool_unit {
+        None => (stakable.address, amount),
+        Some(PoolUnit::One(_, underlying)) | Some(PoolUnit::Two(_, underlying)) | Some(PoolUnit::Multi(_, underlying))
+            if amount == dec!(0) =>
+        {
+            (*underlying, dec!(0))
+        }
+        Some(PoolUnit::One(pool, underlying)) => {
+            let pool: Global<OneResourcePool> = Global::from(*pool);
+            (*underlying, pool.get_redemption_value(amount))
+        }
+        Some(PoolUnit::Two(pool, underlying)) => {
+            let pool: Global<TwoResourcePool> = Global::from(*pool);
+            let redemption = pool.get_redemption_value(amount);
+            (*underlying, redemption.get(underlying).copied().unwrap_or(dec!(0)))
+        }
+        Some(PoolUnit::Multi(pool, underlying)) => {
+            let pool: Global<MultiResourcePool> = Global::from(*pool);
+            let redemption = pool.get_redemption_value(amount);
+            (*underlying, redemption.get(underlying).copied().unwrap_or(dec!(0)))
+        }
+    }
+}
+
+// Calculates the loyalty multiplier after staking continuously for a number of periods, which is the highest multiplier reached in the schedule, capped.
+fn loyalty_multiplier(loyalty: Option<&Loyalty>, periods_staked: i64) -> Decimal {
+    loyalty.map_or(dec!(1), |loyalty| {
+        loyalty
+            .schedule
+            .iter()
+            .filter(|(periods, _)| *periods <= periods_staked)
+            .fold(dec!(1), |highest, (_, multiplier)| if *multiplier > highest { *multiplier } else { highest })
+            .min(loyalty.cap)
+    })
+}
+
+// Calculates the amount of a resource on a staking ID that earns rewards in a period, which excludes stake that is still warming up.
+fn active_stake(resource: &Resource, period: i64) -> Decimal {
+    resource.amount_staked
+        - resource
+            .pending_stake
+            .iter()
+            .filter(|(activation_period, _)| *activation_period > period)
+            .fold(dec!(0), |pending, (_, amount)| pending + *amount)
+}
+
+// Removes an amount of stake from a staking ID, taking it from the stake that is still warming up first, most recently staked first.
+fn remove_stake(resource: &mut Resource, amount: Decimal, period: i64) {
+    resource
+        .pending_stake
+        .retain(|(activation_period, _)| *activation_period > period);
+
+    let mut remaining: Decimal = amount;
+    while remaining > dec!(0) {
+        let Some((activation_period, pending)) = resource.pending_stake.pop() else {
+            break;
+        };
+
+        let removed = if pending > remaining { remaining } else { pending };
+        if pending > removed {
+            resource.pending_stake.push((activation_period, pending - removed));
+        }
+        remaining -= removed;
+    }
+
+    resource.amount_staked -= amount;
+}
+
 #[blueprint]
 mod staking {
     enable_method_auth! {
+        roles {
+            guardian => updatable_by: [OWNER];
+        },
         methods {
             create_id => PUBLIC;
             stake => PUBLIC;
+            stake_basket => PUBLIC;
             start_unstake => PUBLIC;
             finish_unstake => PUBLIC;
+            finish_basket_unstake => PUBLIC;
+            split_transfer_receipt => PUBLIC;
             update_id => PUBLIC;
+            claim_for => PUBLIC;
+            set_payout_destination => PUBLIC;
             update_period => PUBLIC;
             lock_stake => PUBLIC;
+            delegate => PUBLIC;
+            revoke_delegation => PUBLIC;
+            vote_gauges => PUBLIC;
+            get_gauge_votes => PUBLIC;
+            get_stream => PUBLIC;
+            deposit_bribe => PUBLIC;
+            settle_bribe => PUBLIC;
+            deposit_revenue => PUBLIC;
+            get_delegated_weight => PUBLIC;
+            get_delegated_stake_at => PUBLIC;
+            get_stake_at => PUBLIC;
+            get_total_stake_at => PUBLIC;
+            get_current_period => PUBLIC;
+            get_max_owner_lock_duration => PUBLIC;
+            get_max_unstaking_delay => PUBLIC;
+            is_stakable => PUBLIC;
+            get_transfer_receipt_address => PUBLIC;
+            refresh_id => PUBLIC;
+            migrate_id => PUBLIC;
+            deposit_id => PUBLIC;
+            bond_boost => PUBLIC;
+            unbond_boost => PUBLIC;
             set_lock => restrict_to: [OWNER];
+            close_proposal => restrict_to: [OWNER];
             set_period_interval => restrict_to: [OWNER];
             set_rewards => restrict_to: [OWNER];
+            set_target_apr => restrict_to: [OWNER];
+            set_price_ratio => restrict_to: [OWNER];
+            set_warmup => restrict_to: [OWNER];
+            set_loyalty => restrict_to: [OWNER];
+            set_nft_weight => restrict_to: [OWNER];
+            set_stake_access => restrict_to: [OWNER];
+            set_boost => restrict_to: [OWNER];
+            set_gauge => restrict_to: [OWNER];
+            set_oracle => restrict_to: [OWNER];
+            set_id_image => restrict_to: [OWNER];
+            set_id_tiers => restrict_to: [OWNER];
             set_max_claim_delay => restrict_to: [OWNER];
+            set_revenue_tokens => restrict_to: [OWNER];
+            sweep_revenue => restrict_to: [OWNER];
+            set_bribe_token => restrict_to: [OWNER];
+            set_max_bribes => restrict_to: [OWNER];
             fill_rewards => restrict_to: [OWNER];
+            fund_stream => restrict_to: [OWNER];
+            fill_referral_rewards => restrict_to: [OWNER];
+            remove_referral_rewards => restrict_to: [OWNER];
+            set_referral_percentage => restrict_to: [OWNER];
+            set_min_referrer_stake => restrict_to: [OWNER];
             remove_rewards => restrict_to: [OWNER];
             add_stakable => restrict_to: [OWNER];
+            add_basket => restrict_to: [OWNER];
             edit_stakable => restrict_to: [OWNER];
             set_next_period_to_now => restrict_to: [OWNER];
             set_unstake_delay => restrict_to: [OWNER];
+            set_pause => restrict_to: [guardian, OWNER];
+            enable_emergency_exit => restrict_to: [guardian, OWNER];
+            emergency_withdraw => PUBLIC;
         }
     }
 
     struct Staking {
+        // address of the controller badge, the owner of the staking component
+        controller: ResourceAddress,
+        // name of the project, used in the display of staking IDs
+        name: String,
+        // how the key image of staking IDs is determined
+        id_image: IdImage,
+        // tiers of staking IDs, as (minimum total stake, tier name), used in the display of staking IDs
+        id_tiers: Vec<(Decimal, String)>,
         // interval in which rewards are distributed in days
         period_interval: i64,
         // time the next interval starts
@@ -119,16 +529,68 @@ mod staking {
         unstake_delay: i64,
         // resource manager of the staking IDs
         id_manager: ResourceManager,
+        // whether staking IDs can be moved to another account through migrate_id, and only be deposited to accounts by this component
+        soulbound_ids: bool,
         // counter for the staking IDs
         id_counter: u64,
         // vault that stores staking rewards
         reward_vault: FungibleVault,
+        // vault that stores referral rewards, separate from the staking rewards
+        referral_vault: FungibleVault,
+        // referral rewards accrued by staking IDs, but not yet claimed
+        referral_rewards_owed: Decimal,
+        // fraction of claimed staking rewards a referrer receives as referral reward
+        referral_percentage: Decimal,
+        // minimum total stake a referrer needs at the end of the previous period, so fresh staking IDs cannot refer themselves
+        min_referrer_stake: Decimal,
         // keyvaluestore, holding stakable units and their data
         stakes: HashMap<ResourceAddress, StakableUnit>,
+        // boost NFT collection that can be bonded to staking IDs to boost their rewards, if any
+        boost: Option<Boost>,
         // whether a DAO is controlling the staking
         // If a centralized entity controls the controller badge, using the set_lock method, they could lock the someone's tokens by telling the system someone is voting.
         // To prevent this, this functionality only enabled if dao_controlled is set to true.
         dao_controlled: bool,
+        // maximum amount of days the owner can lock a staking ID's stake for a proposal, so stake cannot be frozen indefinitely
+        max_owner_lock_duration: i64,
+        // registry of locks set by the DAO, per staking ID and stakable token, keyed by proposal ID
+        // a staking ID's stake is locked until the latest lock of all proposals that are still open
+        lock_registry: KeyValueStore<NonFungibleLocalId, HashMap<ResourceAddress, DaoLock>>,
+        // proposals that have been closed, of which the locks are released
+        closed_proposals: KeyValueStore<u64, ()>,
+        // keyvaluestore, holding the stake delegated to a delegate per stakable token, as checkpoints of (period, amount)
+        delegated_stake: KeyValueStore<(Delegate, ResourceAddress), Checkpoints>,
+        // keyvaluestore, holding the stake a staking ID has delegated to others per stakable token, as checkpoints of (period, amount)
+        delegated_out: KeyValueStore<(NonFungibleLocalId, ResourceAddress), Checkpoints>,
+        // keyvaluestore, holding the stake of a staking ID per stakable token, as checkpoints of (period, amount)
+        stake_checkpoints: KeyValueStore<(NonFungibleLocalId, ResourceAddress), Checkpoints>,
+        // global emission split across stakables by gauge weights, if None, every stakable distributes its own reward amount
+        gauge: Option<Gauge>,
+        // price oracle component, used to value weight the gauge split, if any
+        oracle: Option<Global<AnyComponent>>,
+        // maximum age in minutes of an oracle price, after which it is considered stale
+        max_price_age: i64,
+        // keyvaluestore, holding the total gauge votes per stakable token, per period
+        gauge_votes: KeyValueStore<i64, HashMap<ResourceAddress, Decimal>>,
+        // keyvaluestore, holding the gauge votes of a staking ID per stakable token, per period
+        id_gauge_votes: KeyValueStore<(i64, NonFungibleLocalId), HashMap<ResourceAddress, Decimal>>,
+        // keyvaluestore, holding the deposited revenue per token, until it is claimed by stakers
+        revenue_vaults: KeyValueStore<ResourceAddress, Vault>,
+        // counter for the bribes
+        bribe_counter: u64,
+        // keyvaluestore, holding all bribes
+        bribes: KeyValueStore<u64, Bribe>,
+        // keyvaluestore, holding the bribes per period and stakable token
+        period_bribes: KeyValueStore<(i64, ResourceAddress), Vec<u64>>,
+        // tokens that can be used as bribes, with the minimum amount of a bribe in that token
+        bribe_tokens: HashMap<ResourceAddress, Decimal>,
+        // maximum amount of bribes that can be deposited per period and stakable token, so claiming bribes stays affordable
+        max_bribes: u64,
+        // which user facing methods are paused, set by the guardian in case a bug is found
+        pause: Pause,
+        // whether the component is in emergency exit mode, in which users can withdraw their stake immediately and rewards are frozen
+        // once enabled, this mode cannot be disabled
+        emergency_exit: bool,
     }
 
     impl Staking {
@@ -140,6 +602,11 @@ mod staking {
         // - `period_interval`: the interval in which rewards are distributed in days
         // - `name`: the name of your project
         // - `symbol`: the symbol of your project
+        // - `dao_controlled`: whether the owner is allowed to lock staked tokens
+        // - `max_unstaking_delay`: the maximum unstaking delay the owner can set
+        // - `max_owner_lock_duration`: the maximum amount of days the owner can lock stake for a proposal
+        // - `soulbound_ids`: whether staking IDs can be moved to another account by their holder through migrate_id, and only be deposited to accounts through this component
+        // - `guardian`: the address of the guardian badge, which can pause the component and enable emergency exit mode
         //
         // ## OUTPUT
         // - the staking component
@@ -156,6 +623,9 @@ mod staking {
             symbol: String,
             dao_controlled: bool,
             max_unstaking_delay: i64,
+            max_owner_lock_duration: i64,
+            soulbound_ids: bool,
+            guardian: ResourceAddress,
         ) -> Global<Staking> {
             let (address_reservation, component_address) =
                 Runtime::allocate_component_address(Staking::blueprint_id());
@@ -186,6 +656,14 @@ mod staking {
                 withdrawer => rule!(deny_all);
                 withdrawer_updater => rule!(deny_all);
             ))
+            .recall_roles(recall_roles!(
+                recaller => if soulbound_ids { rule!(require(global_caller(component_address))) } else { rule!(deny_all) };
+                recaller_updater => rule!(deny_all);
+            ))
+            .deposit_roles(deposit_roles!(
+                depositor => if soulbound_ids { rule!(require(global_caller(component_address))) } else { rule!(allow_all) };
+                depositor_updater => rule!(deny_all);
+            ))
             .non_fungible_data_update_roles(non_fungible_data_update_roles!(
                 non_fungible_data_updater => rule!(require(global_caller(component_address))
                 || require_amount(
@@ -196,7 +674,7 @@ mod staking {
             ))
             .create_with_no_initial_supply();
 
-            let stake_transfer_receipt_manager = ResourceBuilder::new_integer_non_fungible::<UnstakeReceipt>(
+            let stake_transfer_receipt_manager = ResourceBuilder::new_integer_non_fungible::<StakeTransferReceipt>(
                 OwnerRole::Fixed(rule!(require(controller))),
             )
             .metadata(metadata!(
@@ -245,23 +723,55 @@ mod staking {
                 next_period: Clock::current_time_rounded_to_minutes()
                     .add_days(period_interval)
                     .unwrap(),
+                controller,
+                name,
+                id_image: IdImage::Svg,
+                id_tiers: vec![],
                 period_interval,
                 current_period: 0,
                 max_claim_delay: 5,
                 max_unstaking_delay,
                 unstake_delay: 7,
                 id_manager,
+                soulbound_ids,
                 stake_transfer_receipt_manager,
                 stake_transfer_receipt_counter: 0,
                 unstake_receipt_manager,
                 unstake_receipt_counter: 0,
                 id_counter: 0,
+                referral_vault: FungibleVault::new(rewards.resource_address()),
+                referral_rewards_owed: dec!(0),
+                referral_percentage: dec!(0),
+                min_referrer_stake: dec!(0),
                 reward_vault: FungibleVault::with_bucket(rewards.as_fungible()),
                 stakes: HashMap::new(),
+                boost: None,
                 dao_controlled,
+                max_owner_lock_duration,
+                lock_registry: KeyValueStore::new(),
+                closed_proposals: KeyValueStore::new(),
+                delegated_stake: KeyValueStore::new(),
+                delegated_out: KeyValueStore::new(),
+                stake_checkpoints: KeyValueStore::new(),
+                gauge: None,
+                oracle: None,
+                max_price_age: 0,
+                gauge_votes: KeyValueStore::new(),
+                id_gauge_votes: KeyValueStore::new(),
+                revenue_vaults: KeyValueStore::new(),
+                bribe_counter: 0,
+                bribes: KeyValueStore::new(),
+                period_bribes: KeyValueStore::new(),
+                bribe_tokens: HashMap::new(),
+                max_bribes: 10,
+                pause: Pause::default(),
+                emergency_exit: false,
             }
             .instantiate()
             .prepare_to_globalize(OwnerRole::Fixed(rule!(require(controller))))
+            .roles(roles!(
+                guardian => rule!(require(guardian));
+            ))
             .with_address(address_reservation)
             .globalize()
         }
@@ -277,10 +787,22 @@ mod staking {
         // ## LOGIC
         // - the method calculates the number of extra periods that have passed since the last update, because the method might not be called exactly at the end of a period
         // - if a period has passed, for each stakable token the rewards are calculated and recorded, reward calculation is relatively simple:
-        //    - every stakable has a total amount of reward per period
-        //    - total reward amount is divided by the total amount staked to get the reward per staked token
+        //    - every stakable has a total amount of reward per period, or if a gauge is set, a share of the gauge emission according to the gauge weights (or the gauge votes of the period, if any)
+        //    - if the gauge is value weighted, the gauge weights are multiplied by the value of the staked tokens, unless an oracle price is stale
+        //    - if the stakable has a target APR, the amount is instead calculated from the active amount staked (before multipliers), the target APR and the price ratio, capped per period and by the remaining budget, and the stakable is left out of the gauge split
+        //    - pool unit stakables are valued by the redeemable amount of their underlying token, for both value weighting and the target APR
+        //    - the amount of a funded reward stream scheduled for the period is added to it
+        //    - stakables with revenue tokens distribute no rewards, and are left out of the gauge split
+        //    - total reward amount is divided by the total weight (amount staked excluding stake that is still warming up, multiplied by boost and loyalty multipliers) to get the reward per unit of weight
+        // - revenue deposited to a stakable during the period is divided by its weight instead, if the weight is zero, the revenue carries over to the next period
         // - the current period is incremented and the next period is set
+        // - pending weight that activates in the new period starts earning rewards
+        // - if the component is in emergency exit mode, rewards are frozen and nothing happens
         pub fn update_period(&mut self) {
+            if self.emergency_exit {
+                return;
+            }
+
             let extra_periods_dec: Decimal = ((Clock::current_time_rounded_to_minutes()
                 .seconds_since_unix_epoch
                 - self.next_period.seconds_since_unix_epoch)
@@ -291,18 +813,106 @@ mod staking {
             let extra_periods: i64 = i64::try_from(extra_periods_dec.0 / Decimal::ONE.0).unwrap();
 
             if Clock::current_time_is_at_or_after(self.next_period, TimePrecision::Minute) {
-                for (_address, stakable_unit) in self.stakes.iter_mut() {
-                    if stakable_unit.amount_staked > dec!(0) {
+                let gauge_split: Option<(Decimal, HashMap<ResourceAddress, Decimal>)> =
+                    self.gauge.as_ref().map(|gauge| {
+                        let votes = self
+                            .gauge_votes
+                            .get(&self.current_period)
+                            .map(|votes| votes.clone())
+                            .filter(|votes| votes.values().any(|vote| *vote > dec!(0)));
+                        (gauge.emission, votes.unwrap_or(gauge.weights.clone()))
+                    });
+                let gauge_split = match gauge_split {
+                    Some((emission, weights)) if self.gauge.as_ref().unwrap().value_weighted => {
+                        Some((emission, self.value_weights(weights)))
+                    }
+                    gauge_split => gauge_split,
+                };
+                let total_gauge_weight: Decimal = gauge_split.as_ref().map_or(dec!(0), |(_, weights)| {
+                    weights
+                        .iter()
+                        .filter(|(address, _)| {
+                            self.stakes
+                                .get(address)
+                                .is_some_and(|stakable_unit| {
+                                    stakable_unit.revenue_tokens.is_none() && stakable_unit.target_apr.is_none()
+                                })
+                        })
+                        .fold(dec!(0), |total, (_, weight)| total + *weight)
+                });
+
+                for (address, stakable_unit) in self.stakes.iter_mut() {
+                    let mut reward_amount = match &gauge_split {
+                        Some((emission, weights)) if total_gauge_weight > dec!(0) => {
+                            *emission * weights.get(address).copied().unwrap_or(dec!(0)) / total_gauge_weight
+                        }
+                        Some(_) => dec!(0),
+                        None => stakable_unit.reward_amount,
+                    };
+
+                    if stakable_unit.revenue_tokens.is_some() {
+                        reward_amount = dec!(0);
+                    } else if stakable_unit.target_apr.is_some() {
+                        let (_, active_amount) = staked_value(stakable_unit, stakable_unit.active_amount);
+                        let target_apr = stakable_unit.target_apr.as_mut().unwrap();
+                        reward_amount = if stakable_unit.weight > dec!(0) {
+                            (active_amount
+                                * target_apr.price_ratio
+                                * target_apr.apr
+                                * Decimal::from(self.period_interval)
+                                / dec!(365))
+                            .min(target_apr.max_per_period)
+                            .min(target_apr.budget)
+                        } else {
+                            dec!(0)
+                        };
+                        target_apr.budget -= reward_amount;
+                    }
+
+                    if let Some((_, stream_amount)) = stakable_unit
+                        .stream
+                        .iter()
+                        .find(|(stream_period, _)| *stream_period == self.current_period)
+                        .filter(|_| stakable_unit.revenue_tokens.is_none())
+                    {
+                        reward_amount += *stream_amount;
+                    }
+                    stakable_unit
+                        .stream
+                        .retain(|(stream_period, _)| *stream_period > self.current_period);
+
+                    if stakable_unit.weight > dec!(0) {
                         stakable_unit.rewards.insert(
                             self.current_period,
-                            stakable_unit.reward_amount / stakable_unit.amount_staked,
+                            reward_amount / stakable_unit.weight,
                         );
                     } else {
                         stakable_unit.rewards.insert(self.current_period, dec!(0));
                     }
+
+                    if stakable_unit.weight > dec!(0) && !stakable_unit.pending_revenue.is_empty() {
+                        let weight = stakable_unit.weight;
+                        let unclaimed: HashMap<ResourceAddress, Decimal> =
+                            stakable_unit.pending_revenue.drain().collect();
+                        let revenue: HashMap<ResourceAddress, Decimal> = unclaimed
+                            .iter()
+                            .map(|(token, amount)| (*token, *amount / weight))
+                            .collect();
+                        stakable_unit.revenue.insert(self.current_period, revenue);
+                        stakable_unit.unclaimed_revenue.insert(self.current_period, unclaimed);
+                    }
                 }
 
                 self.current_period += 1;
+
+                for (_address, stakable_unit) in self.stakes.iter_mut() {
+                    if let Some(activated) = stakable_unit.pending_weight.remove(&self.current_period) {
+                        stakable_unit.weight += activated;
+                    }
+                    if let Some(activated) = stakable_unit.pending_amount.remove(&self.current_period) {
+                        stakable_unit.active_amount += activated;
+                    }
+                }
                 self.next_period = self
                     .next_period
                     .add_days((1 + extra_periods) * self.period_interval)
@@ -316,15 +926,24 @@ mod staking {
         // - `address`: the address of the stakable token
         // - `amount`: the amount of tokens to unstake
         // - `stake_transfer`: whether to transfer the staked tokens to another user
+        // - `stake_tokens`: the stake tokens of the unstaked amount, required if the stakable has a stake token
+        // - `nfts`: the NFTs to unstake, required if the stakable is an NFT collection, in which case the amount is ignored
         //
         // ## OUTPUT
         // - the unstake receipt / transfer receipt
         //
         // ## LOGIC
+        // - the method checks whether unstaking is paused
         // - the method checks the staking ID
+        // - the method checks if latest rewards have been claimed, if not, the method fails (the unclaimed periods still need the pending stake that the unstake removes)
         // - the method checks the staked amount
-        // - the method checks if the staked tokens are locked (then unstaking is not possible)
-        // - if not, tokens are removed from staking ID stake
+        // - the method checks if the staked tokens are locked, either by the user or by an open proposal in the lock registry, also of the staking ID they are delegated to (then unstaking is not possible)
+        // - if not, tokens are removed from staking ID stake (and from the stake delegated by it), and stake checkpoints are updated
+        // - stake that is still warming up is unstaked first
+        // - the staking duration of the loyalty multiplier is reset, or decayed proportionally if the stakable decays it
+        // - the weight of the staking ID in the stakable is recalculated, like when staking
+        // - the method refreshes the display of the staking ID
+        // - if the stakable has a stake token, the stake tokens of the unstaked amount are burned
         // - if the user wants to transfer the tokens, a transfer receipt is minted
         // - if the user wants to unstake the tokens, an unstake receipt is minted
         pub fn start_unstake(
@@ -333,12 +952,19 @@ mod staking {
             address: ResourceAddress,
             amount: Decimal,
             stake_transfer: bool,
+            stake_tokens: Option<Bucket>,
+            nfts: Option<Vec<NonFungibleLocalId>>,
         ) -> Bucket {
+            assert!(!self.pause.unstake, "Unstaking is currently paused.");
             let id_proof =
                 id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
 
             let id = id_proof.non_fungible::<Id>().local_id().clone();
             let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before unstaking."
+            );
 
             let mut unstake_amount: Decimal = amount;
             let mut resource_map = id_data.resources.clone();
@@ -353,25 +979,77 @@ mod staking {
             );
 
             if let Some(locked_until) = resource.locked_until {
+                assert!(
+                    Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute),
+                    "You cannot unstake tokens currently locked."
+                );
+            }
+
+            if let Some(locked_until) = self.dao_locked_until(&id, address) {
                 assert!(
                     Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute),
                     "You cannot unstake tokens currently participating in a vote."
                 );
             }
+            self.check_delegate_unlocked(&id_data.delegations, address);
 
-            if amount >= resource.amount_staked {
-                unstake_amount = resource.amount_staked;
-                resource.amount_staked = dec!(0);
+            let mut unstaked_nfts: Vec<NonFungibleLocalId> = vec![];
+            if address.is_fungible() {
+                if amount >= resource.amount_staked {
+                    unstake_amount = resource.amount_staked;
+                }
+            } else {
+                assert!(!stake_transfer, "Stake transfers are not supported for NFT stakables.");
+                unstaked_nfts = nfts.expect("NFTs to unstake required.");
+                assert!(!unstaked_nfts.is_empty(), "NFTs to unstake required.");
+                unstake_amount = dec!(0);
+                for nft in unstaked_nfts.iter() {
+                    unstake_amount += resource.nfts.remove(nft).expect("NFT not staked to this staking ID.");
+                }
+            }
+
+            self.apply_weight(address, &resource, id_data.boost_multiplier, false);
+            remove_stake(&mut resource, unstake_amount, self.current_period);
+            let decay = self
+                .stakes
+                .get(&address)
+                .unwrap()
+                .loyalty
+                .as_ref()
+                .is_some_and(|loyalty| loyalty.decay);
+            if decay && resource.amount_staked > dec!(0) {
+                let periods_staked = Decimal::from(self.current_period - resource.stake_start)
+                    * resource.amount_staked
+                    / (resource.amount_staked + unstake_amount);
+                let periods_staked = periods_staked.checked_floor().unwrap();
+                resource.stake_start = self.current_period - i64::try_from(periods_staked.0 / Decimal::ONE.0).unwrap();
             } else {
-                resource.amount_staked -= amount;
+                resource.stake_start = self.current_period;
             }
+            self.apply_weight(address, &resource, id_data.boost_multiplier, true);
 
-            self.stakes.get_mut(&address).unwrap().amount_staked -= resource.amount_staked;
+            self.stakes.get_mut(&address).unwrap().amount_staked -= unstake_amount;
+            self.update_stake_checkpoints(&id, address, resource.amount_staked);
 
             resource_map.insert(address, resource);
 
+            if let Some(delegate) = id_data.delegations.get(&address) {
+                self.update_delegated_stake(&id, delegate.clone(), address, -unstake_amount);
+            }
+
             self.id_manager
                 .update_non_fungible_data(&id, "resources", resource_map);
+            self.refresh_id(id);
+
+            if let Some(stake_token) = &self.stakes.get(&address).unwrap().stake_token {
+                let stake_tokens = stake_tokens.expect("Stake tokens of the unstaked amount required.");
+                assert!(
+                    stake_tokens.resource_address() == stake_token.address()
+                        && stake_tokens.amount() == unstake_amount,
+                    "Invalid stake tokens supplied!"
+                );
+                stake_tokens.burn();
+            }
 
             if stake_transfer {
                 let stake_transfer_receipt = StakeTransferReceipt {
@@ -390,6 +1068,7 @@ mod staking {
                     redemption_time: Clock::current_time_rounded_to_minutes()
                         .add_days(self.unstake_delay)
                         .unwrap(),
+                    nfts: unstaked_nfts,
                 };
                 self.unstake_receipt_counter += 1;
                 self.unstake_receipt_manager.mint_non_fungible(
@@ -402,64 +1081,143 @@ mod staking {
         // This method finishes an unstake, redeeming the unstaked tokens
         //
         // ## INPUT
-        // - `receipt`: the unstake receipt
+        // - `receipt`: the unstake receipt, or a stake transfer receipt in emergency exit mode
         //
         // ## OUTPUT
         // - the unstaked tokens
         //
         // ## LOGIC
-        // - the method checks the receipt
-        // - the method checks the redemption time
+        // - the method checks whether unstaking is paused
+        // - the method checks the receipt, accepting stake transfer receipts only in emergency exit mode
+        // - the method checks the redemption time, unless the component is in emergency exit mode
         // - the method burns the receipt
-        // - the method returns the unstaked tokens
+        // - the method returns the unstaked tokens, or the unstaked NFTs
         pub fn finish_unstake(&mut self, receipt: Bucket) -> Bucket {
-            assert!(receipt.resource_address() == self.unstake_receipt_manager.address());
+            let receipt_data = self.redeem_unstake_receipt(receipt);
+            assert!(
+                self.stakes.get(&receipt_data.address).unwrap().basket.is_none(),
+                "Use finish_basket_unstake to redeem a basket."
+            );
 
-            let receipt_data = receipt
-                .as_non_fungible()
-                .non_fungible::<UnstakeReceipt>()
-                .data();
+            let vault = &mut self.stakes.get_mut(&receipt_data.address).unwrap().vault;
+            if receipt_data.nfts.is_empty() {
+                vault.take(receipt_data.amount)
+            } else {
+                let nfts: IndexSet<NonFungibleLocalId> = receipt_data.nfts.into_iter().collect();
+                vault.as_non_fungible().take_non_fungibles(&nfts).into()
+            }
+        }
+
+        // This method finishes an unstake of a basket, redeeming all its legs
+        //
+        // ## INPUT
+        // - `receipt`: the unstake receipt of the basket, or a stake transfer receipt of the basket in emergency exit mode
+        //
+        // ## OUTPUT
+        // - the unstaked tokens, one bucket per leg
+        //
+        // ## LOGIC
+        // - the method checks whether unstaking is paused
+        // - the method checks the receipt, accepting stake transfer receipts only in emergency exit mode
+        // - the method checks the redemption time, unless the component is in emergency exit mode
+        // - the method burns the receipt
+        // - the method releases the leg tokens of the unstaked basket units
+        pub fn finish_basket_unstake(&mut self, receipt: Bucket) -> Vec<Bucket> {
+            let receipt_data = self.redeem_unstake_receipt(receipt);
+            assert!(
+                self.stakes.get(&receipt_data.address).unwrap().basket.is_some(),
+                "Receipt is not for a basket, use finish_unstake."
+            );
+
+            self.release_basket(receipt_data.address, receipt_data.amount)
+        }
 
+        // This method splits a stake transfer receipt in two, so part of the transferred stake can be redeemed separately
+        //
+        // ## INPUT
+        // - `receipt`: the stake transfer receipt
+        // - `amount`: the amount of staked tokens to split off
+        //
+        // ## OUTPUT
+        // - a stake transfer receipt for the split off amount
+        // - a stake transfer receipt for the remaining amount
+        //
+        // ## LOGIC
+        // - the method checks the receipt and the amount
+        // - the method burns the receipt
+        // - the method mints two new receipts, together holding the amount of the burned receipt
+        pub fn split_transfer_receipt(&mut self, receipt: NonFungibleBucket, amount: Decimal) -> (Bucket, Bucket) {
             assert!(
-                Clock::current_time_is_at_or_after(
-                    receipt_data.redemption_time,
-                    TimePrecision::Minute
-                ),
-                "You cannot unstake tokens before the redemption time."
+                receipt.resource_address() == self.stake_transfer_receipt_manager.address()
+                    && receipt.amount() == dec!(1),
+                "Invalid stake transfer receipt supplied!"
             );
 
+            let receipt_data = receipt.non_fungible::<StakeTransferReceipt>().data();
+            assert!(
+                amount > dec!(0) && amount < receipt_data.amount,
+                "Split amount needs to be positive and below the receipt amount."
+            );
             receipt.burn();
 
-            self.stakes
-                .get_mut(&receipt_data.address)
-                .unwrap()
-                .vault
-                .take(receipt_data.amount)
+            self.stake_transfer_receipt_counter += 1;
+            let split_receipt = self.stake_transfer_receipt_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.stake_transfer_receipt_counter),
+                StakeTransferReceipt {
+                    address: receipt_data.address,
+                    amount,
+                },
+            );
+            self.stake_transfer_receipt_counter += 1;
+            let remaining_receipt = self.stake_transfer_receipt_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.stake_transfer_receipt_counter),
+                StakeTransferReceipt {
+                    address: receipt_data.address,
+                    amount: receipt_data.amount - amount,
+                },
+            );
+
+            (split_receipt, remaining_receipt)
         }
 
         // This method creates a new staking ID
         //
         // ## INPUT
-        // - none
+        // - `referrer`: an optional staking ID that referred the user
         //
         // ## OUTPUT
         // - the staking ID
         //
         // ## LOGIC
+        // - the method checks whether the referrer exists, and had stake at the end of the previous period of at least the minimum referrer stake
         // - the method increments the ID counter
-        // - the method creates a new ID
+        // - the method creates a new ID, with its name, description and key image
         // - the method returns the ID
-        pub fn create_id(&mut self) -> Bucket {
+        pub fn create_id(&mut self, referrer: Option<NonFungibleLocalId>) -> Bucket {
+            if let Some(referrer) = &referrer {
+                assert!(self.id_manager.non_fungible_exists(referrer), "Referrer not found.");
+                assert!(self.is_eligible_referrer(referrer), "Referrer does not have enough stake.");
+            }
+
             self.id_counter += 1;
+            let id = NonFungibleLocalId::integer(self.id_counter);
+            let (name, description, key_image_url) = self.id_display(&id, &HashMap::new(), dec!(1));
 
             let id_data = Id {
+                name,
+                description,
+                key_image_url,
                 resources: HashMap::new(),
                 next_period: self.current_period + 1,
+                delegations: HashMap::new(),
+                boost_nfts: vec![],
+                boost_multiplier: dec!(1),
+                referrer,
+                referral_rewards: dec!(0),
+                payout_destination: None,
             };
 
-            let id: Bucket = self
-                .id_manager
-                .mint_non_fungible(&NonFungibleLocalId::integer(self.id_counter), id_data);
+            let id: Bucket = self.id_manager.mint_non_fungible(&id, id_data);
 
             id
         }
@@ -471,28 +1229,50 @@ mod staking {
         // - `stake_bucket`: an optional bucket of the staked tokens
         // - `id_proof`: the proof of the staking ID
         // - `stake_transfer_receipt`: an optional stake transfer receipt
+        // - `access_proofs`: the proofs that the staker is allowed to stake the stakable token, one per access rule, so a basket can be staked if its legs require different proofs
+        // - `referrer`: an optional staking ID that referred the user, only used if a new staking ID is created
+        // - `account`: the account stake tokens are deposited to, required if the stakable has a stake token
         //
         // ## OUTPUT
-        // - none
+        // - the newly created staking ID, if no staking ID was supplied
         //
         // ## LOGIC
+        // - the method checks whether staking is paused or the component is in emergency exit mode
         // - the method checks whether a staking ID is supplied, if not, it creates one
         // - the method checks the staking ID
         // - the method checks if latest rewards have been claimed, if not, the method fails
         // - the method checks whether it received tokens or a transfer receipt
         // - the method adds tokens to an internal vault, or burns the transfer receipt
-        // - the method updates the staking ID
-        pub fn stake(&mut self, stake_bucket: Bucket, id_proof: Option<Proof>) -> Option<Bucket> {
+        // - staked NFTs count as their weight, and are recorded on the staking ID
+        // - the method checks whether the staker is allowed to stake the stakable, and every leg if it is a basket
+        // - the method checks the stake limits of the stakable, and of every leg if it is a basket, counting leg tokens held for baskets
+        // - if the stakable has a warm-up, the staked tokens are pending until the warm-up has passed
+        // - the start of the stake moves to the average start of the existing and added stake
+        // - the weight of the staking ID in the stakable is recalculated as its stake multiplied by its boost and loyalty multipliers, with its changes scheduled at the end of the warm-up and at every step of the loyalty schedule
+        // - the method updates the staking ID, the stake delegated by it, and the stake checkpoints
+        // - if the stakable has a stake token, stake tokens of the staked amount are minted and deposited to the account
+        // - the method refreshes the display of the staking ID
+        pub fn stake(
+            &mut self,
+            stake_bucket: Bucket,
+            id_proof: Option<Proof>,
+            access_proofs: Vec<AccessProof>,
+            referrer: Option<NonFungibleLocalId>,
+            account: Option<ComponentAddress>,
+        ) -> Option<Bucket> {
+            assert!(!self.pause.stake, "Staking is currently paused.");
+            assert!(!self.emergency_exit, "Staking is disabled in emergency exit mode.");
             let id: NonFungibleLocalId;
-            let id_bucket: Option<Bucket> = None;
+            let mut id_bucket: Option<Bucket> = None;
 
             if let Some(id_proof) = id_proof {
                 let id_proof =
                     id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
                 id = id_proof.as_non_fungible().non_fungible::<Id>().local_id().clone();
             } else {
-                let id_bucket = self.create_id();
-                id = id_bucket.as_non_fungible().non_fungible::<Id>().local_id().clone();
+                let new_id_bucket = self.create_id(referrer);
+                id = new_id_bucket.as_non_fungible().non_fungible::<Id>().local_id().clone();
+                id_bucket = Some(new_id_bucket);
             }
 
             let id_data: Id = self.id_manager.get_non_fungible_data(&id);
@@ -503,27 +1283,83 @@ mod staking {
 
             let stake_amount: Decimal;
             let address: ResourceAddress;
+            let mut staked_nfts: HashMap<NonFungibleLocalId, Decimal> = HashMap::new();
 
             if stake_bucket.resource_address() == self.stake_transfer_receipt_manager.address() {
                 (stake_amount, address) = self.stake_transfer_receipt(stake_bucket.as_non_fungible());
             } else {
-                (stake_amount, address) = self.stake_tokens(stake_bucket);
+                (stake_amount, address, staked_nfts) = self.stake_tokens(stake_bucket);
+            }
+
+            let basket_legs = self.stakes.get(&address).unwrap().basket.clone();
+            for (leg, ratio) in basket_legs.iter().flatten() {
+                self.check_access(*leg, &access_proofs);
+                self.check_limits(*leg, &id_data.resources, stake_amount * *ratio);
             }
+            self.check_access(address, &access_proofs);
+            self.check_limits(address, &id_data.resources, stake_amount);
+
+            let stakable = self.stakes.get(&address).unwrap();
+            assert!(stake_amount >= stakable.limits.min_stake, "Stake amount is below the minimum stake.");
+            let warmup_periods = stakable.warmup_periods;
 
             let mut resource_map = id_data.resources.clone();
-            resource_map.entry(address)
-                .and_modify(|resource| {
-                    resource.amount_staked += stake_amount;
-                })
-                .or_insert(Resource {
-                    amount_staked: stake_amount,
-                    locked_until: None,
-                });
+            let resource = resource_map.entry(address).or_insert(Resource {
+                amount_staked: dec!(0),
+                locked_until: None,
+                pending_stake: vec![],
+                nfts: HashMap::new(),
+                stake_start: self.current_period,
+            });
+            resource
+                .pending_stake
+                .retain(|(activation_period, _)| *activation_period > self.current_period);
+            self.apply_weight(address, resource, id_data.boost_multiplier, false);
+
+            resource.nfts.extend(staked_nfts);
+            resource.amount_staked += stake_amount;
+            let id_amount_staked = resource.amount_staked;
+
+            if warmup_periods > 0 {
+                let activation_period = self.current_period + warmup_periods;
+                match resource
+                    .pending_stake
+                    .iter_mut()
+                    .find(|(pending_period, _)| *pending_period == activation_period)
+                {
+                    Some((_, pending)) => *pending += stake_amount,
+                    None => resource.pending_stake.push((activation_period, stake_amount)),
+                }
+            }
+
+            let previous_amount_staked = id_amount_staked - stake_amount;
+            if previous_amount_staked > dec!(0) {
+                let stake_start = ((previous_amount_staked * Decimal::from(resource.stake_start)
+                    + stake_amount * Decimal::from(self.current_period))
+                    / id_amount_staked)
+                    .checked_floor()
+                    .unwrap();
+                resource.stake_start = i64::try_from(stake_start.0 / Decimal::ONE.0).unwrap();
+            } else {
+                resource.stake_start = self.current_period;
+            }
+            self.apply_weight(address, resource, id_data.boost_multiplier, true);
 
             self.id_manager
                 .update_non_fungible_data(&id, "resources", resource_map);
 
             self.stakes.get_mut(&address).unwrap().amount_staked += stake_amount;
+            self.update_stake_checkpoints(&id, address, id_amount_staked);
+
+            if let Some(delegate) = id_data.delegations.get(&address) {
+                self.update_delegated_stake(&id, delegate.clone(), address, stake_amount);
+            }
+
+            if let Some(stake_token) = &self.stakes.get(&address).unwrap().stake_token {
+                let account: Global<Account> =
+                    Global::from(account.expect("Account to deposit stake tokens to required."));
+                account.try_deposit_or_abort(stake_token.mint(stake_amount), None);
+            }
 
             self.id_manager.update_non_fungible_data(
                 &id,
@@ -531,89 +1367,196 @@ mod staking {
                 self.current_period + 1,
             );
 
+            self.refresh_id(id);
+
             id_bucket
         }
 
-        // This method claims rewards from a staking ID
+        // This method stakes a basket of tokens to a staking ID
         //
         // ## INPUT
+        // - `legs`: a bucket of every leg token of the basket
+        // - `basket`: the address of the basket
         // - `id_proof`: the proof of the staking ID
+        // - `access_proofs`: the proofs that the staker is allowed to stake the basket and its legs, one per access rule
+        // - `referrer`: an optional staking ID that referred the user, only used if a new staking ID is created
+        // - `account`: the account stake tokens are deposited to, required if the basket has a stake token
         //
         // ## OUTPUT
-        // - the claimed rewards
+        // - the newly created staking ID, if no staking ID was supplied
+        // - the leg tokens that did not fit the fixed ratio of the basket
         //
         // ## LOGIC
-        // - the method updates the component period if necessary
-        // - the method checks the staking ID
-        // - the method checks amount of unclaimed periods
-        // - the method iterates over all staked tokens and calculates the rewards
-        // - the method updates the staking ID to the next period
-        // - the method returns the claimed rewards
-        pub fn update_id(&mut self, id_proof: NonFungibleProof) -> FungibleBucket {
-            self.update_period();
-            let id_proof =
-                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
-            let id = id_proof.non_fungible::<Id>().local_id().clone();
-            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+        // - the method checks whether every leg of the basket is supplied once
+        // - the method calculates the amount of basket units the legs are sufficient for
+        // - the method puts the required amount of every leg in the vault of that leg, rounded up
+        // - the method mints the basket units and stakes them, checking the access rules and stake limits of the basket and its legs
+        // - the method returns the remaining leg tokens
+        pub fn stake_basket(
+            &mut self,
+            legs: Vec<Bucket>,
+            basket: ResourceAddress,
+            id_proof: Option<Proof>,
+            access_proofs: Vec<AccessProof>,
+            referrer: Option<NonFungibleLocalId>,
+            account: Option<ComponentAddress>,
+        ) -> (Option<Bucket>, Vec<Bucket>) {
+            let ratios = self
+                .stakes
+                .get(&basket)
+                .and_then(|stakable| stakable.basket.clone())
+                .expect("Basket not found.");
+            let supplied: IndexSet<ResourceAddress> = legs.iter().map(|leg| leg.resource_address()).collect();
+            assert!(
+                supplied.len() == legs.len()
+                    && supplied.len() == ratios.len()
+                    && supplied.iter().all(|leg| ratios.contains_key(leg)),
+                "Supply every leg of the basket exactly once."
+            );
 
-            let mut claimed_weeks: i64 = self.current_period - id_data.next_period + 1;
-            if claimed_weeks > self.max_claim_delay {
-                claimed_weeks = self.max_claim_delay;
+            let units = legs
+                .iter()
+                .map(|leg| leg.amount() / ratios[&leg.resource_address()])
+                .min()
+                .unwrap();
+            assert!(units > dec!(0), "Not enough tokens supplied for a basket unit.");
+
+            let mut remainder: Vec<Bucket> = vec![];
+            for mut leg in legs {
+                let leg_address = leg.resource_address();
+                let deposit = leg.take_advanced(
+                    units * ratios[&leg_address],
+                    WithdrawStrategy::Rounded(RoundingMode::ToPositiveInfinity),
+                );
+                self.stakes.get_mut(&leg_address).unwrap().vault.put(deposit);
+                remainder.push(leg);
             }
 
-            assert!(claimed_weeks > 0, "Wait longer to claim your rewards.");
-
-            let mut staking_reward: Decimal = dec!(0);
-
-            self.id_manager
-                .update_non_fungible_data(&id, "next_period", self.current_period + 1);
-
-            for (address, stakable_unit) in self.stakes.iter() {
-                for week in 1..(claimed_weeks + 1) {
-                    if stakable_unit
-                        .rewards
-                        .get(&(self.current_period - week))
-                        .is_some()
-                    {
-                        staking_reward += *stakable_unit
-                            .rewards
-                            .get(&(self.current_period - week))
-                            .unwrap()
-                            * id_data
-                                .resources
-                                .get(&address)
-                                .map_or(dec!(0), |resource| resource.amount_staked);
-                    }
-                }
-            }
+            let basket_units = ResourceManager::from(basket).mint(units);
+            let id_bucket = self.stake(basket_units, id_proof, access_proofs, referrer, account);
 
-            self.reward_vault.take(staking_reward)
+            (id_bucket, remainder)
         }
 
-        // This method locks staked tokens for a certain duration and gives rewards for locking them
+        // This method claims rewards from a staking ID
         //
         // ## INPUT
-        // - `address`: the address of the stakable token
         // - `id_proof`: the proof of the staking ID
         //
         // ## OUTPUT
-        // - rewards for locking the tokens
+        // - the claimed rewards
+        // - the claimed revenue and bribes, one bucket per token
         //
         // ## LOGIC
+        // - the method checks whether claiming is paused or the component is in emergency exit mode
+        // - the method updates the component period if necessary
         // - the method checks the staking ID
-        // - the method checks whether this resource address is lockable
-        // - the method checks whether the staking ID tokens are already locked
-        // - the method locks the tokens by updating the staking ID
-        // - the method returns the rewards for locking the tokens
-
-
-        pub fn lock_stake(&mut self, address: ResourceAddress, id_proof: NonFungibleProof) -> FungibleBucket {
+        // - the method claims the revenue, bribes and rewards of the staking ID
+        // - the method returns the claimed rewards, revenue and bribes
+        pub fn update_id(&mut self, id_proof: NonFungibleProof) -> (FungibleBucket, Vec<Bucket>) {
+            assert!(!self.pause.claim, "Claiming rewards is currently paused.");
+            assert!(!self.emergency_exit, "Rewards are frozen in emergency exit mode.");
+            self.update_period();
             let id_proof =
                 id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
             let id = id_proof.non_fungible::<Id>().local_id().clone();
-            let stakable = self.stakes.get(&address).unwrap();
 
-            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let mut revenue = self.claim_revenue(&id);
+            revenue.extend(self.claim_bribes(&id));
+            (self.claim_rewards(&id), revenue)
+        }
+
+        // This method claims rewards from a staking ID to the payout destination set on it, and can be called by anyone
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether claiming is paused or the component is in emergency exit mode
+        // - the method updates the component period if necessary
+        // - the method checks whether the staking ID has a payout destination
+        // - the method claims the revenue, bribes and rewards of the staking ID
+        // - the method deposits the claimed rewards, revenue and bribes to the payout destination
+        pub fn claim_for(&mut self, id: NonFungibleLocalId) {
+            assert!(!self.pause.claim, "Claiming rewards is currently paused.");
+            assert!(!self.emergency_exit, "Rewards are frozen in emergency exit mode.");
+            self.update_period();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let payout_destination = id_data
+                .payout_destination
+                .expect("No payout destination set on this staking ID.");
+
+            let revenue = self.claim_revenue(&id);
+            let bribes = self.claim_bribes(&id);
+            let mut payout: Vec<Bucket> = vec![self.claim_rewards(&id).into()];
+            payout.extend(revenue);
+            payout.extend(bribes);
+
+            match payout_destination {
+                PayoutDestination::Account(account) => {
+                    let account: Global<Account> = Global::from(account);
+                    account.try_deposit_batch_or_abort(payout, None);
+                }
+                PayoutDestination::Component(component, method) => {
+                    let component: Global<AnyComponent> = Global::from(component);
+                    for bucket in payout {
+                        component.call_raw::<()>(&method, scrypto_args!(bucket));
+                    }
+                }
+            }
+        }
+
+        // This method sets the payout destination of a staking ID, to which claim_for deposits its rewards
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `payout_destination`: the payout destination, or None to revoke it
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method records the payout destination on the staking ID
+        pub fn set_payout_destination(&mut self, id_proof: NonFungibleProof, payout_destination: Option<PayoutDestination>) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            self.id_manager
+                .update_non_fungible_data(&id, "payout_destination", payout_destination);
+        }
+
+        // This method locks staked tokens for a certain duration and gives rewards for locking them
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `id_proof`: the proof of the staking ID
+        //
+        // ## OUTPUT
+        // - rewards for locking the tokens
+        //
+        // ## LOGIC
+        // - the method checks whether locking is paused or the component is in emergency exit mode
+        // - the method checks the staking ID
+        // - the method checks whether this resource address is lockable
+        // - the method checks whether the staking ID tokens are already locked
+        // - the method locks the tokens by updating the staking ID
+        // - the method returns the rewards for locking the tokens
+
+
+        pub fn lock_stake(&mut self, address: ResourceAddress, id_proof: NonFungibleProof) -> FungibleBucket {
+            assert!(!self.pause.lock, "Locking is currently paused.");
+            assert!(!self.emergency_exit, "Locking is disabled in emergency exit mode.");
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let stakable = self.stakes.get(&address).unwrap();
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
             let mut resource_map = id_data.resources.clone();
             let mut resource = resource_map
                 .get(&address)
@@ -636,119 +1579,2085 @@ mod staking {
             self.reward_vault.take(stakable.lock.payment * amount_staked)
         }
 
-        //////////////////////////////////////////////////////////////////////
-        ////////////////////////////ADMIN METHODS/////////////////////////////
-        //////////////////////////////////////////////////////////////////////
+        // This method bonds boost NFTs to a staking ID, boosting its rewards
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `boost_nfts`: the boost NFTs to bond
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks if latest rewards have been claimed, if not, the method fails
+        // - the method puts the boost NFTs in the boost vault and records them on the staking ID
+        // - the method updates the boost multiplier of the staking ID, and the weight of all its staked tokens
+        // - the method refreshes the display of the staking ID
+        pub fn bond_boost(&mut self, id_proof: NonFungibleProof, boost_nfts: NonFungibleBucket) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before bonding boost NFTs."
+            );
 
-        pub fn set_period_interval(&mut self, new_interval: i64) {
-            self.period_interval = new_interval;
-        }
+            let boost = self.boost.as_mut().expect("No boost available.");
+            assert!(boost_nfts.resource_address() == boost.address, "Invalid boost NFT supplied!");
 
-        pub fn fill_rewards(&mut self, bucket: Bucket) {
-            self.reward_vault.put(bucket.as_fungible());
-        }
+            let mut bonded_nfts = id_data.boost_nfts.clone();
+            bonded_nfts.extend(boost_nfts.non_fungible_local_ids());
+            boost.vault.put(boost_nfts);
 
-        pub fn remove_rewards(&mut self, amount: Decimal) -> Bucket {
-            self.reward_vault.take(amount).into()
+            self.update_boost(&id, &id_data, bonded_nfts);
+            self.refresh_id(id);
         }
 
-        pub fn set_max_claim_delay(&mut self, new_delay: i64) {
-            self.max_claim_delay = new_delay;
-        }
+        // This method unbonds boost NFTs from a staking ID
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `boost_nfts`: the local IDs of the boost NFTs to unbond
+        //
+        // ## OUTPUT
+        // - the boost NFTs
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks if latest rewards have been claimed, if not, the method fails
+        // - the method checks whether the boost NFTs are bonded to the staking ID
+        // - the method updates the boost multiplier of the staking ID, and the weight of all its staked tokens
+        // - the method refreshes the display of the staking ID
+        // - the method returns the boost NFTs
+        pub fn unbond_boost(&mut self, id_proof: NonFungibleProof, boost_nfts: Vec<NonFungibleLocalId>) -> NonFungibleBucket {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before unbonding boost NFTs."
+            );
 
-        pub fn set_unstake_delay(&mut self, new_delay: i64) {
-            assert!(new_delay <= self.max_unstaking_delay, "Unstaking delay cannot be longer than the maximum unstaking delay.");
-            self.unstake_delay = new_delay;
+            let mut bonded_nfts = id_data.boost_nfts.clone();
+            for boost_nft in boost_nfts.iter() {
+                let index = bonded_nfts
+                    .iter()
+                    .position(|bonded_nft| bonded_nft == boost_nft)
+                    .expect("Boost NFT not bonded to staking ID.");
+                bonded_nfts.remove(index);
+            }
+
+            self.update_boost(&id, &id_data, bonded_nfts);
+            self.refresh_id(id);
+
+            let boost_nfts: IndexSet<NonFungibleLocalId> = boost_nfts.into_iter().collect();
+            self.boost
+                .as_mut()
+                .unwrap()
+                .vault
+                .take_non_fungibles(&boost_nfts)
         }
 
-        pub fn set_rewards(&mut self, address: ResourceAddress, reward: Decimal) {
-            self.stakes.get_mut(&address).unwrap().reward_amount = reward;
+        // This method moves a soulbound staking ID to another account
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `new_account`: the account to move the staking ID to
+        // - `id_vault`: the address of the vault of the account currently holding the staking ID
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether staking IDs are soulbound
+        // - the method checks the staking ID, and drops the proof so the staking ID can be recalled
+        // - the method recalls the proven staking ID from the vault, which only this component is allowed to do
+        // - the method deposits the staking ID to the new account
+        pub fn migrate_id(&mut self, id_proof: NonFungibleProof, new_account: ComponentAddress, id_vault: InternalAddress) {
+            assert!(self.soulbound_ids, "Staking IDs are not soulbound and can not be moved.");
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            id_proof.drop();
+
+            let id_bucket = self.recall_id(id_vault, id);
+            self.deposit_id(id_bucket, new_account);
         }
 
-        pub fn add_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
-            self.stakes.insert(
-                address,
-                StakableUnit {
-                    address,
-                    amount_staked: dec!(0),
-                    vault: Vault::new(address),
-                    reward_amount,
-                    lock,
-                    rewards: KeyValueStore::new(),
-                },
+        // This method deposits a staking ID to an account, which is how a new soulbound staking ID returned by create_id or stake reaches an account
+        //
+        // ## INPUT
+        // - `id_bucket`: the bucket with the staking ID
+        // - `account`: the account to deposit the staking ID to
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method deposits the staking ID to the account, which only this component is allowed to do for soulbound staking IDs
+        // - as staking IDs can not be withdrawn, the bucket can only hold a newly created staking ID
+        pub fn deposit_id(&mut self, id_bucket: NonFungibleBucket, account: ComponentAddress) {
+            assert!(
+                id_bucket.resource_address() == self.id_manager.address(),
+                "Invalid Id supplied!"
             );
-        }
 
-        pub fn edit_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
-            let stakable = self.stakes.get_mut(&address).unwrap();
-            stakable.reward_amount = reward_amount;
-            stakable.lock = lock;
+            let account: Global<Account> = Global::from(account);
+            account.try_deposit_or_abort(id_bucket.into(), None);
         }
 
-        pub fn set_next_period_to_now(&mut self) {
-            self.next_period = Clock::current_time_rounded_to_minutes();
+        // This method refreshes the name, description and key image of a staking ID
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method renders the display of the staking ID from its current stake summary
+        // - the method updates the name, description and key image of the staking ID
+        pub fn refresh_id(&self, id: NonFungibleLocalId) {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let (name, description, key_image_url) =
+                self.id_display(&id, &id_data.resources, id_data.boost_multiplier);
+
+            self.id_manager.update_non_fungible_data(&id, "name", name);
+            self.id_manager
+                .update_non_fungible_data(&id, "description", description);
+            self.id_manager
+                .update_non_fungible_data(&id, "key_image_url", key_image_url);
         }
 
-        // This method locks staked tokens for voting
+        // This method delegates the voting power of staked tokens to another staking ID or account
         //
         // ## INPUT
+        // - `id_proof`: the proof of the staking ID
         // - `address`: the address of the stakable token
-        // - `lock_until`: the date until which the tokens are locked
-        // - `id`: the staking ID
+        // - `delegate`: the staking ID or account to delegate to
         //
         // ## OUTPUT
         // - none
         //
         // ## LOGIC
-        // - the method checks whether a DAO is controlling the staking
-        // - the method updates the locked_until field of the staking ID appropriately
-        
-        pub fn set_lock(&mut self, address: ResourceAddress, lock_until: Instant, id: NonFungibleLocalId) {
-            assert!(self.dao_controlled, "This functionality is only available if a DAO is controlling the staking.");
+        // - the method checks the staking ID
+        // - the method checks the staking ID is not delegating to itself
+        // - if the stake was already delegated, the method checks whether the previous delegate is not voting with it, and removes it from the previous delegate
+        // - the staked amount is added to the delegate
+        // - the method records the delegation on the staking ID, rewards are still claimed by the staking ID itself
+        pub fn delegate(&mut self, id_proof: NonFungibleProof, address: ResourceAddress, delegate: Delegate) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
             let id_data: Id = self.id_manager.get_non_fungible_data(&id);
 
-            let mut resource_map = id_data.resources.clone();
-            let mut resource = resource_map
+            assert!(delegate != Delegate::Id(id.clone()), "Cannot delegate to yourself.");
+            self.check_delegate_unlocked(&id_data.delegations, address);
+
+            let amount_staked = id_data
+                .resources
                 .get(&address)
                 .expect("Stakable not found in staking ID.")
-                .clone();
-               
-            resource.locked_until = Some(lock_until);
-            resource_map.insert(address, resource);
+                .amount_staked;
+
+            let mut delegations = id_data.delegations.clone();
+            if let Some(previous_delegate) = delegations.insert(address, delegate.clone()) {
+                self.update_delegated_stake(&id, previous_delegate, address, -amount_staked);
+            }
+            self.update_delegated_stake(&id, delegate, address, amount_staked);
 
             self.id_manager
-                .update_non_fungible_data(&id, "resources", resource_map);
+                .update_non_fungible_data(&id, "delegations", delegations);
         }
 
-        //////////////////////////////////////////////////////////////////////
-        ////////////////////////////HELPER METHODS////////////////////////////
-        //////////////////////////////////////////////////////////////////////
+        // This method revokes a delegation of voting power
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `address`: the address of the stakable token
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks whether the delegate is not voting with the delegated stake
+        // - the method removes the staked amount from the delegate
+        // - the method removes the delegation from the staking ID
+        pub fn revoke_delegation(&mut self, id_proof: NonFungibleProof, address: ResourceAddress) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
 
-        /// This method counts the staked tokens and puts them away in the staking component's vault.
-        /// 
-        /// ## INPUT
-        /// - `stake_bucket`: the bucket of staked tokens
-        ///
-        /// ## OUTPUT
-        /// - the amount of staked tokens
-        /// - the address of the stakable token
-        /// 
-        /// ## LOGIC
-        /// - the method checks whether the staked token is a stakable token
-        /// - the method puts the staked tokens in the staking component's vault
-        /// - the method returns the amount of staked tokens and the address of the stakable token
+            self.check_delegate_unlocked(&id_data.delegations, address);
+            let mut delegations = id_data.delegations.clone();
+            let delegate = delegations
+                .remove(&address)
+                .expect("Stakable is not delegated.");
 
-        fn stake_tokens(&mut self, stake_bucket: Bucket) -> (Decimal, ResourceAddress) {   
-            let address: ResourceAddress = stake_bucket.resource_address();
-            assert!(self.stakes.get(&address).is_some(), "Token supplied does not match requested stakable token.");
-            let stake_amount: Decimal = stake_bucket.amount();
-            self.stakes
-                .get_mut(&address)
-                .unwrap()
-                .vault
-                .put(stake_bucket);
+            let amount_staked = id_data
+                .resources
+                .get(&address)
+                .map_or(dec!(0), |resource| resource.amount_staked);
+            self.update_delegated_stake(&id, delegate, address, -amount_staked);
+
+            self.id_manager
+                .update_non_fungible_data(&id, "delegations", delegations);
+        }
+
+        // This method returns the stake delegated to a delegate at a given period
+        //
+        // ## INPUT
+        // - `delegate`: the staking ID or account delegated to
+        // - `address`: the address of the stakable token
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the amount of stake delegated to the delegate
+        //
+        // ## LOGIC
+        // - the method finds the latest checkpoint of the delegate at or before the period
+        pub fn get_delegated_weight(&self, delegate: Delegate, address: ResourceAddress, period: i64) -> Decimal {
+            self.delegated_stake
+                .get(&(delegate, address))
+                .map_or(dec!(0), |checkpoints| read_checkpoint(&checkpoints, period))
+        }
+
+        // This method returns the stake a staking ID has delegated to others at a given period
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `address`: the address of the stakable token
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the amount of stake the staking ID delegated, which does not count towards its own voting power
+        //
+        // ## LOGIC
+        // - the method finds the latest checkpoint of the staking ID at or before the period
+        pub fn get_delegated_stake_at(&self, id: NonFungibleLocalId, address: ResourceAddress, period: i64) -> Decimal {
+            self.delegated_out
+                .get(&(id, address))
+                .map_or(dec!(0), |checkpoints| read_checkpoint(&checkpoints, period))
+        }
+
+        // This method allocates the gauge voting weight of a staking ID to stakables for the current period
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `allocation`: the fraction of its voting weight the staking ID allocates to each stakable token, adding up to at most 1
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether gauge voting is enabled, not paused and the component is not in emergency exit mode
+        // - the method updates the component period if necessary
+        // - the method checks the staking ID and the allocation
+        // - the voting weight is the stake of the vote resource at the end of the previous period, multiplied by the boost multiplier (preventing flash-stake voting)
+        // - a previous allocation of the staking ID in the current period is replaced
+        // - the method records the votes of the staking ID and adds them to the total gauge votes of the current period
+        pub fn vote_gauges(&mut self, id_proof: NonFungibleProof, allocation: HashMap<ResourceAddress, Decimal>) {
+            let vote_resource = self
+                .gauge
+                .as_ref()
+                .and_then(|gauge| gauge.vote_resource)
+                .expect("Gauge voting is not enabled.");
+            assert!(!self.pause.vote, "Gauge voting is currently paused.");
+            assert!(!self.emergency_exit, "Gauge voting is disabled in emergency exit mode.");
+            self.update_period();
+
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            let total_allocation = allocation.values().fold(dec!(0), |total, fraction| {
+                assert!(*fraction >= dec!(0), "Allocation cannot be negative.");
+                total + *fraction
+            });
+            assert!(total_allocation <= dec!(1), "Allocation cannot exceed the full voting weight.");
+            for address in allocation.keys() {
+                assert!(self.stakes.contains_key(address), "Stakable not found.");
+            }
+
+            let vote_weight = self.get_stake_at(id.clone(), vote_resource, self.current_period - 1)
+                * id_data.boost_multiplier;
+            assert!(vote_weight > dec!(0), "No vote weight available.");
+
+            let mut total_votes = self
+                .gauge_votes
+                .get(&self.current_period)
+                .map_or(HashMap::new(), |votes| votes.clone());
+            if let Some(previous_votes) = self.id_gauge_votes.get(&(self.current_period, id.clone())) {
+                for (address, votes) in previous_votes.iter() {
+                    *total_votes.entry(*address).or_insert(dec!(0)) -= *votes;
+                }
+            }
+
+            let id_votes: HashMap<ResourceAddress, Decimal> = allocation
+                .into_iter()
+                .map(|(address, fraction)| (address, vote_weight * fraction))
+                .collect();
+            for (address, votes) in id_votes.iter() {
+                *total_votes.entry(*address).or_insert(dec!(0)) += *votes;
+            }
+
+            self.gauge_votes.insert(self.current_period, total_votes);
+            self.id_gauge_votes.insert((self.current_period, id), id_votes);
+        }
+
+        // This method returns the total gauge votes per stakable token of a period
+        pub fn get_gauge_votes(&self, period: i64) -> HashMap<ResourceAddress, Decimal> {
+            self.gauge_votes
+                .get(&period)
+                .map_or(HashMap::new(), |votes| votes.clone())
+        }
+
+        // This method deposits a bribe for the staking IDs voting for a stakable in a gauge period
+        //
+        // ## INPUT
+        // - `bucket`: the bribe, in a whitelisted bribe token
+        // - `address`: the address of the stakable token the bribe is for
+        // - `period`: the period the bribe is for, which cannot have ended yet
+        // - `refund_account`: the account unclaimed bribes are returned to, or None to roll them forward
+        //
+        // ## OUTPUT
+        // - the ID of the bribe
+        //
+        // ## LOGIC
+        // - the method checks the stakable token, the bribe token and its minimum amount, and the period
+        // - the method stores the bribe and adds it to the bribes of the period and stakable token, if the maximum amount of bribes is not reached yet
+        pub fn deposit_bribe(
+            &mut self,
+            bucket: Bucket,
+            address: ResourceAddress,
+            period: i64,
+            refund_account: Option<ComponentAddress>,
+        ) -> u64 {
+            assert!(self.stakes.contains_key(&address), "Stakable not found.");
+            let min_amount = *self
+                .bribe_tokens
+                .get(&bucket.resource_address())
+                .expect("Bribe token not whitelisted.");
+            assert!(
+                bucket.amount() > dec!(0) && bucket.amount() >= min_amount,
+                "Bribe is smaller than the minimum bribe amount."
+            );
+            self.update_period();
+            assert!(period >= self.current_period, "Cannot bribe a period that has already ended.");
+
+            self.insert_bribe(bucket.as_fungible(), address, period, refund_account, true)
+        }
+
+        // This method deposits revenue to be distributed to the stakers of a stakable at the end of the current period
+        //
+        // ## INPUT
+        // - `bucket`: the revenue, in one of the revenue tokens of the stakable
+        // - `target_stakable`: the address of the stakable token whose stakers receive the revenue
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether the component is in emergency exit mode
+        // - the method checks the stakable token, and whether it accepts the revenue token
+        // - the method updates the component period if necessary
+        // - the method puts the revenue in the revenue vault of its token
+        // - the method adds the revenue to the revenue of the stakable in the current period
+        pub fn deposit_revenue(&mut self, bucket: Bucket, target_stakable: ResourceAddress) {
+            assert!(!self.emergency_exit, "Revenue cannot be deposited in emergency exit mode.");
+            let accepted = self
+                .stakes
+                .get(&target_stakable)
+                .expect("Stakable not found.")
+                .revenue_tokens
+                .as_ref()
+                .is_some_and(|revenue_tokens| revenue_tokens.contains(&bucket.resource_address()));
+            assert!(accepted, "Revenue token not accepted by this stakable.");
+            self.update_period();
+
+            let token = bucket.resource_address();
+            let amount = bucket.amount();
+            let existing_vault = self.revenue_vaults.get(&token).is_some();
+            if existing_vault {
+                self.revenue_vaults.get_mut(&token).unwrap().put(bucket);
+            } else {
+                self.revenue_vaults.insert(token, Vault::with_bucket(bucket));
+            }
+
+            *self
+                .stakes
+                .get_mut(&target_stakable)
+                .unwrap()
+                .pending_revenue
+                .entry(token)
+                .or_insert(dec!(0)) += amount;
+        }
+
+        // This method settles a bribe of which the claim delay has passed, or for which no votes were cast
+        //
+        // ## INPUT
+        // - `bribe_id`: the ID of the bribe
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method updates the component period if necessary
+        // - the method checks whether the period of the bribe has ended, and whether the claim delay has passed or no votes were cast for the stakable in that period
+        // - the method returns the unclaimed tokens to the refund account, or rolls them forward to the current period
+        pub fn settle_bribe(&mut self, bribe_id: u64) {
+            self.update_period();
+
+            let (address, refund_account, unclaimed) = {
+                let mut bribe = self.bribes.get_mut(&bribe_id).expect("Bribe not found.");
+                assert!(bribe.amount > dec!(0), "Bribe is already settled.");
+                assert!(bribe.period < self.current_period, "The period of the bribe has not ended yet.");
+
+                let votes = self
+                    .gauge_votes
+                    .get(&bribe.period)
+                    .and_then(|votes| votes.get(&bribe.address).copied())
+                    .unwrap_or(dec!(0));
+                assert!(
+                    self.current_period - bribe.period > self.max_claim_delay || votes <= dec!(0),
+                    "Bribe can still be claimed."
+                );
+
+                bribe.amount = dec!(0);
+                (bribe.address, bribe.refund_account, bribe.vault.take_all())
+            };
+
+            if unclaimed.amount() == dec!(0) {
+                unclaimed.drop_empty();
+                return;
+            }
+
+            match refund_account {
+                Some(refund_account) => {
+                    let refund_account: Global<Account> = Global::from(refund_account);
+                    refund_account.try_deposit_or_abort(unclaimed.into(), None);
+                }
+                None => {
+                    self.insert_bribe(unclaimed, address, self.current_period, None, false);
+                }
+            }
+        }
+
+        // This method returns the stake of a staking ID at a given period
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `address`: the address of the stakable token
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the amount of tokens staked to the staking ID at the end of the period (or now, if the period has not ended yet)
+        //
+        // ## LOGIC
+        // - the method finds the latest checkpoint of the staking ID at or before the period
+        pub fn get_stake_at(&self, id: NonFungibleLocalId, address: ResourceAddress, period: i64) -> Decimal {
+            self.stake_checkpoints
+                .get(&(id, address))
+                .map_or(dec!(0), |checkpoints| read_checkpoint(&checkpoints, period))
+        }
+
+        // This method returns the total stake of a stakable token at a given period
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the total amount of tokens staked at the end of the period (or now, if the period has not ended yet)
+        //
+        // ## LOGIC
+        // - the method finds the latest checkpoint of the stakable token at or before the period
+        pub fn get_total_stake_at(&self, address: ResourceAddress, period: i64) -> Decimal {
+            read_checkpoint(
+                &self.stakes.get(&address).expect("Stakable not found.").checkpoints,
+                period,
+            )
+        }
+
+        pub fn get_current_period(&self) -> i64 {
+            self.current_period
+        }
+
+        pub fn get_max_owner_lock_duration(&self) -> i64 {
+            self.max_owner_lock_duration
+        }
+
+        pub fn get_max_unstaking_delay(&self) -> i64 {
+            self.max_unstaking_delay
+        }
+
+        pub fn is_stakable(&self, address: ResourceAddress) -> bool {
+            self.stakes.get(&address).is_some()
+        }
+
+        pub fn get_transfer_receipt_address(&self) -> ResourceAddress {
+            self.stake_transfer_receipt_manager.address()
+        }
+
+        // This method withdraws all staked tokens of a staking ID when the component is in emergency exit mode
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `stake_tokens`: the stake tokens of the staked amounts, one bucket per stakable token with a stake token
+        //
+        // ## OUTPUT
+        // - the staked tokens, one bucket per stakable token
+        //
+        // ## LOGIC
+        // - the method checks whether the component is in emergency exit mode
+        // - the method checks the staking ID
+        // - the method takes the staked amount of every stakable token from its vault, ignoring locks and the unstake delay
+        // - if the stakable has a stake token, the stake tokens of the staked amount are burned, so the supply keeps mirroring the amount staked
+        // - the method sets all staked amounts on the staking ID to zero
+        // - the method returns the staked tokens, and bonded boost NFTs
+        pub fn emergency_withdraw(&mut self, id_proof: NonFungibleProof, stake_tokens: Vec<Bucket>) -> Vec<Bucket> {
+            assert!(self.emergency_exit, "Emergency withdrawal is only available in emergency exit mode.");
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            let mut stake_tokens: HashMap<ResourceAddress, Bucket> = stake_tokens
+                .into_iter()
+                .map(|bucket| (bucket.resource_address(), bucket))
+                .collect();
+
+            let mut resource_map = id_data.resources.clone();
+            let mut buckets: Vec<Bucket> = vec![];
+
+            for (address, resource) in resource_map.iter_mut() {
+                if resource.amount_staked > dec!(0) {
+                    if let Some(delegate) = id_data.delegations.get(address) {
+                        self.update_delegated_stake(&id, delegate.clone(), *address, -resource.amount_staked);
+                    }
+
+                    let amount_staked = resource.amount_staked;
+                    self.apply_weight(*address, resource, id_data.boost_multiplier, false);
+                    resource.pending_stake.clear();
+
+                    if let Some(stake_token) = &self.stakes.get(address).unwrap().stake_token {
+                        let stake_token_bucket = stake_tokens
+                            .remove(&stake_token.address())
+                            .expect("Stake tokens of the staked amount required.");
+                        assert!(stake_token_bucket.amount() == amount_staked, "Invalid stake tokens supplied!");
+                        stake_token_bucket.burn();
+                    }
+
+                    let stakable = self.stakes.get_mut(address).unwrap();
+                    stakable.amount_staked -= resource.amount_staked;
+                    if stakable.basket.is_some() {
+                        buckets.extend(self.release_basket(*address, amount_staked));
+                    } else if address.is_fungible() {
+                        buckets.push(stakable.vault.take(resource.amount_staked));
+                    } else {
+                        let nfts: IndexSet<NonFungibleLocalId> = resource.nfts.drain().map(|(nft, _)| nft).collect();
+                        buckets.push(stakable.vault.as_non_fungible().take_non_fungibles(&nfts).into());
+                    }
+                    resource.amount_staked = dec!(0);
+                    resource.locked_until = None;
+                    self.update_stake_checkpoints(&id, *address, dec!(0));
+                }
+            }
+
+            assert!(stake_tokens.is_empty(), "Invalid stake tokens supplied!");
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+
+            if !id_data.boost_nfts.is_empty() {
+                let boost_nfts: IndexSet<NonFungibleLocalId> = id_data.boost_nfts.into_iter().collect();
+                buckets.push(
+                    self.boost
+                        .as_mut()
+                        .unwrap()
+                        .vault
+                        .take_non_fungibles(&boost_nfts)
+                        .into(),
+                );
+                self.id_manager
+                    .update_non_fungible_data(&id, "boost_nfts", Vec::<NonFungibleLocalId>::new());
+            }
+
+            self.refresh_id(id);
+
+            buckets
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////ADMIN METHODS/////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        pub fn set_period_interval(&mut self, new_interval: i64) {
+            self.period_interval = new_interval;
+        }
+
+        pub fn fill_rewards(&mut self, bucket: Bucket) {
+            self.reward_vault.put(bucket.as_fungible());
+        }
+
+        // This method funds a reward stream, dripping rewards evenly over a number of periods
+        //
+        // ## INPUT
+        // - `bucket`: the rewards, in the reward token
+        // - `stakable`: the address of the stakable token whose stakers receive the rewards
+        // - `periods`: the number of periods to drip the rewards over, starting with the current period
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the stakable token is not a revenue stakable, the rewards and the number of periods
+        // - the method puts the rewards in the reward vault
+        // - the method adds an even share of the rewards to the stream amount of each period, on top of earlier fundings
+        pub fn fund_stream(&mut self, bucket: Bucket, stakable: ResourceAddress, periods: i64) {
+            assert!(
+                self.stakes.get(&stakable).expect("Stakable not found.").revenue_tokens.is_none(),
+                "Revenue stakables can not be funded with a stream."
+            );
+            assert!(periods > 0, "Stream needs to last at least one period.");
+            assert!(
+                bucket.resource_address() == self.reward_vault.resource_address(),
+                "Stream needs to be funded with the reward token."
+            );
+            self.update_period();
+
+            let total_amount = bucket.amount();
+            let period_amount = total_amount / Decimal::from(periods);
+            self.reward_vault.put(bucket.as_fungible());
+
+            let stream = &mut self.stakes.get_mut(&stakable).unwrap().stream;
+            for i in 0..periods {
+                let period = self.current_period + i;
+                let amount = if i == periods - 1 {
+                    total_amount - period_amount * Decimal::from(periods - 1)
+                } else {
+                    period_amount
+                };
+                match stream.iter_mut().find(|(stream_period, _)| *stream_period == period) {
+                    Some((_, stream_amount)) => *stream_amount += amount,
+                    None => stream.push((period, amount)),
+                }
+            }
+            stream.sort_by_key(|(stream_period, _)| *stream_period);
+        }
+
+        // This method returns the remaining reward stream of a stakable, as (period, amount)
+        pub fn get_stream(&self, stakable: ResourceAddress) -> Vec<(i64, Decimal)> {
+            self.stakes
+                .get(&stakable)
+                .expect("Stakable not found.")
+                .stream
+                .clone()
+        }
+
+        pub fn remove_rewards(&mut self, amount: Decimal) -> Bucket {
+            self.reward_vault.take(amount).into()
+        }
+
+        pub fn fill_referral_rewards(&mut self, bucket: Bucket) {
+            self.referral_vault.put(bucket.as_fungible());
+        }
+
+        pub fn remove_referral_rewards(&mut self, amount: Decimal) -> Bucket {
+            assert!(
+                amount <= self.referral_vault.amount() - self.referral_rewards_owed,
+                "Cannot remove referral rewards that are owed to referrers."
+            );
+            self.referral_vault.take(amount).into()
+        }
+
+        pub fn set_referral_percentage(&mut self, percentage: Decimal) {
+            assert!(percentage >= dec!(0) && percentage <= dec!(1), "Referral percentage must be between 0 and 1.");
+            self.referral_percentage = percentage;
+        }
+
+        pub fn set_min_referrer_stake(&mut self, min_referrer_stake: Decimal) {
+            assert!(min_referrer_stake >= dec!(0), "Minimum referrer stake cannot be negative.");
+            self.min_referrer_stake = min_referrer_stake;
+        }
+
+        pub fn set_max_claim_delay(&mut self, new_delay: i64) {
+            self.max_claim_delay = new_delay;
+        }
+
+        pub fn set_bribe_token(&mut self, address: ResourceAddress, min_amount: Option<Decimal>) {
+            match min_amount {
+                Some(min_amount) => {
+                    assert!(address.is_fungible(), "Bribe token needs to be fungible.");
+                    assert!(min_amount > dec!(0), "Minimum bribe amount needs to be positive.");
+                    self.bribe_tokens.insert(address, min_amount);
+                }
+                None => {
+                    self.bribe_tokens.remove(&address);
+                }
+            }
+        }
+
+        pub fn set_max_bribes(&mut self, max_bribes: u64) {
+            self.max_bribes = max_bribes;
+        }
+
+        pub fn set_revenue_tokens(&mut self, address: ResourceAddress, revenue_tokens: Option<Vec<ResourceAddress>>) {
+            for token in revenue_tokens.iter().flatten() {
+                assert!(token.is_fungible(), "Revenue needs to be fungible.");
+            }
+            self.stakes.get_mut(&address).expect("Stakable not found.").revenue_tokens = revenue_tokens;
+        }
+
+        // This method takes the revenue of a period that stakers did not claim within the max claim delay
+        pub fn sweep_revenue(&mut self, address: ResourceAddress, period: i64) -> Vec<Bucket> {
+            self.update_period();
+            assert!(self.current_period - period > self.max_claim_delay, "Revenue can still be claimed.");
+
+            let stakable_unit = self.stakes.get_mut(&address).expect("Stakable not found.");
+            stakable_unit.revenue.remove(&period);
+            let unclaimed = stakable_unit
+                .unclaimed_revenue
+                .remove(&period)
+                .expect("No unclaimed revenue in this period.");
+
+            unclaimed
+                .into_iter()
+                .filter(|(_, amount)| *amount > dec!(0))
+                .map(|(token, amount)| {
+                    let mut vault = self.revenue_vaults.get_mut(&token).unwrap();
+                    let amount = amount.min(vault.amount());
+                    vault.take_advanced(amount, WithdrawStrategy::Rounded(RoundingMode::ToZero))
+                })
+                .collect()
+        }
+
+        pub fn set_unstake_delay(&mut self, new_delay: i64) {
+            assert!(new_delay <= self.max_unstaking_delay, "Unstaking delay cannot be longer than the maximum unstaking delay.");
+            self.unstake_delay = new_delay;
+        }
+
+        pub fn set_rewards(&mut self, address: ResourceAddress, reward: Decimal) {
+            self.stakes.get_mut(&address).expect("Stakable not found.").reward_amount = reward;
+        }
+
+        pub fn set_target_apr(&mut self, address: ResourceAddress, target_apr: Option<TargetApr>) {
+            if let Some(target_apr) = &target_apr {
+                assert!(
+                    target_apr.apr >= dec!(0)
+                        && target_apr.price_ratio >= dec!(0)
+                        && target_apr.max_per_period >= dec!(0)
+                        && target_apr.budget >= dec!(0),
+                    "Target APR values cannot be negative."
+                );
+            }
+            self.stakes.get_mut(&address).unwrap().target_apr = target_apr;
+        }
+
+        pub fn set_price_ratio(&mut self, address: ResourceAddress, price_ratio: Decimal) {
+            assert!(price_ratio >= dec!(0), "Price ratio cannot be negative.");
+            self.stakes
+                .get_mut(&address)
+                .unwrap()
+                .target_apr
+                .as_mut()
+                .expect("Stakable has no target APR.")
+                .price_ratio = price_ratio;
+        }
+
+        pub fn set_boost(&mut self, address: ResourceAddress, table: BoostTable) {
+            match &mut self.boost {
+                Some(boost) => {
+                    assert!(boost.address == address, "A different boost NFT collection is already registered.");
+                    boost.table = table;
+                }
+                None => {
+                    self.boost = Some(Boost {
+                        address,
+                        table,
+                        vault: NonFungibleVault::new(address),
+                    });
+                }
+            }
+        }
+
+        pub fn set_gauge(&mut self, gauge: Option<Gauge>) {
+            if let Some(vote_resource) = gauge.as_ref().and_then(|gauge| gauge.vote_resource) {
+                assert!(self.stakes.contains_key(&vote_resource), "Vote resource needs to be a stakable token.");
+            }
+            if gauge.as_ref().is_some_and(|gauge| gauge.value_weighted) {
+                assert!(self.oracle.is_some(), "Value weighting requires a price oracle.");
+            }
+            self.gauge = gauge;
+        }
+
+        pub fn set_oracle(&mut self, oracle: Option<ComponentAddress>, max_price_age: i64) {
+            assert!(max_price_age > 0, "Maximum price age needs to be positive.");
+            self.oracle = oracle.map(Global::from);
+            self.max_price_age = max_price_age;
+        }
+
+        pub fn set_id_image(&mut self, id_image: IdImage) {
+            self.id_image = id_image;
+        }
+
+        pub fn set_id_tiers(&mut self, id_tiers: Vec<(Decimal, String)>) {
+            self.id_tiers = id_tiers;
+        }
+
+        pub fn set_stake_access(&mut self, address: ResourceAddress, access: StakeAccess) {
+            self.stakes.get_mut(&address).unwrap().access = access;
+        }
+
+        pub fn set_nft_weight(&mut self, address: ResourceAddress, nft_weight: NftWeight) {
+            assert!(!address.is_fungible(), "NFT weights can only be set for NFT stakables.");
+            self.stakes.get_mut(&address).unwrap().nft_weight = Some(nft_weight);
+        }
+
+        pub fn set_loyalty(&mut self, address: ResourceAddress, loyalty: Option<Loyalty>) {
+            if let Some(loyalty) = &loyalty {
+                assert!(loyalty.cap >= dec!(1), "Loyalty cap cannot be below 1.");
+                assert!(!loyalty.schedule.is_empty(), "Loyalty schedule cannot be empty.");
+                assert!(
+                    loyalty
+                        .schedule
+                        .iter()
+                        .all(|(periods, multiplier)| *periods >= 0 && *multiplier >= dec!(0)),
+                    "Loyalty schedule cannot be negative."
+                );
+                assert!(
+                    loyalty
+                        .schedule
+                        .windows(2)
+                        .all(|steps| steps[0].0 < steps[1].0 && steps[0].1 <= steps[1].1),
+                    "Loyalty schedule needs to be increasing in periods and multipliers."
+                );
+            }
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            assert!(stakable.amount_staked == dec!(0), "Loyalty can only be changed while nothing is staked.");
+            stakable.loyalty = loyalty;
+        }
+
+        pub fn set_warmup(&mut self, address: ResourceAddress, warmup_periods: i64) {
+            assert!(warmup_periods >= 0, "Warm-up cannot be negative.");
+            self.stakes.get_mut(&address).unwrap().warmup_periods = warmup_periods;
+        }
+
+        // This method adds a stakable token
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `reward_amount`: the amount of rewards distributed every period
+        // - `lock`: the locking options
+        // - `limits`: the stake limits
+        // - `stake_token`: whether to mint a stake token to stakers
+        // - `pool_unit`: the native pool the stakable is a pool unit of, and the underlying token it is valued in, if any
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - if the stakable is a pool unit, the method checks whether it belongs to the pool
+        // - if a stake token is requested, it is created, only depositable by the staking component, so it cannot be transferred
+        // - the stakable unit is added
+        pub fn add_stakable(
+            &mut self,
+            address: ResourceAddress,
+            reward_amount: Decimal,
+            lock: Lock,
+            limits: StakeLimits,
+            stake_token: bool,
+            pool_unit: Option<PoolUnit>,
+        ) {
+            if let Some(PoolUnit::One(pool, _) | PoolUnit::Two(pool, _) | PoolUnit::Multi(pool, _)) = &pool_unit {
+                let unit_pool: Option<GlobalAddress> = ResourceManager::from(address)
+                    .get_metadata("pool")
+                    .ok()
+                    .flatten();
+                assert!(
+                    unit_pool == Some(GlobalAddress::from(*pool)),
+                    "Stakable is not a pool unit of the given pool."
+                );
+            }
+
+            let stake_token: Option<ResourceManager> = if stake_token {
+                let component_address = Runtime::global_address();
+                let symbol: String = ResourceManager::from(address)
+                    .get_metadata("symbol")
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+
+                Some(
+                    ResourceBuilder::new_fungible(OwnerRole::Fixed(rule!(require(self.controller))))
+                        .metadata(metadata!(
+                            init {
+                                "name" => format!("Staked {}", symbol), updatable;
+                                "symbol" => format!("st{}", symbol), updatable;
+                                "description" => format!("A token representing your staked {}. It is not transferable.", symbol), updatable;
+                            }
+                        ))
+                        .mint_roles(mint_roles!(
+                            minter => rule!(require(global_caller(component_address)));
+                            minter_updater => rule!(deny_all);
+                        ))
+                        .burn_roles(burn_roles!(
+                            burner => rule!(require(global_caller(component_address)));
+                            burner_updater => rule!(deny_all);
+                        ))
+                        .deposit_roles(deposit_roles!(
+                            depositor => rule!(require(global_caller(component_address)));
+                            depositor_updater => rule!(deny_all);
+                        ))
+                        .create_with_no_initial_supply(),
+                )
+            } else {
+                None
+            };
+
+            self.stakes.insert(
+                address,
+                StakableUnit {
+                    address,
+                    amount_staked: dec!(0),
+                    vault: Vault::new(address),
+                    reward_amount,
+                    lock,
+                    limits,
+                    access: StakeAccess::Public,
+                    rewards: KeyValueStore::new(),
+                    checkpoints: Checkpoints::new(),
+                    warmup_periods: 0,
+                    weight: dec!(0),
+                    pending_weight: KeyValueStore::new(),
+                    active_amount: dec!(0),
+                    pending_amount: KeyValueStore::new(),
+                    stake_token,
+                    revenue_tokens: None,
+                    pending_revenue: HashMap::new(),
+                    revenue: KeyValueStore::new(),
+                    unclaimed_revenue: KeyValueStore::new(),
+                    stream: vec![],
+                    target_apr: None,
+                    pool_unit,
+                    nft_weight: None,
+                    basket: None,
+                    loyalty: None,
+                },
+            );
+        }
+
+        // This method adds a basket, a stakable that requires fixed-ratio deposits of several stakable tokens
+        //
+        // ## INPUT
+        // - `name`: the name of the basket
+        // - `legs`: the amount of every leg token required per basket unit
+        // - `reward_amount`: the amount of rewards distributed every period
+        // - `lock`: the locking options
+        // - `limits`: the stake limits, in basket units
+        //
+        // ## OUTPUT
+        // - the address of the basket units
+        //
+        // ## LOGIC
+        // - the method checks whether every leg is a fungible stakable token, which is not a basket itself
+        // - the method creates the basket unit, only mintable and burnable by the staking component, which never leaves the component
+        // - the basket is added as a stakable unit of its basket unit, with its own rewards
+        pub fn add_basket(
+            &mut self,
+            name: String,
+            legs: HashMap<ResourceAddress, Decimal>,
+            reward_amount: Decimal,
+            lock: Lock,
+            limits: StakeLimits,
+        ) -> ResourceAddress {
+            assert!(legs.len() > 1, "A basket needs at least two legs.");
+            for (leg, ratio) in legs.iter() {
+                assert!(
+                    leg.is_fungible()
+                        && self
+                            .stakes
+                            .get(leg)
+                            .is_some_and(|stakable| stakable.basket.is_none()),
+                    "Basket legs need to be fungible stakable tokens."
+                );
+                assert!(*ratio > dec!(0), "Basket ratios need to be positive.");
+            }
+
+            let component_address = Runtime::global_address();
+            let basket_manager = ResourceBuilder::new_fungible(OwnerRole::Fixed(rule!(require(self.controller))))
+                .metadata(metadata!(
+                    init {
+                        "name" => format!("{} Basket Unit", name), updatable;
+                        "description" => format!("An internal unit of the {} staking basket.", name), updatable;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+            let address = basket_manager.address();
+
+            self.add_stakable(address, reward_amount, lock, limits, false, None);
+            self.stakes.get_mut(&address).unwrap().basket = Some(legs);
+
+            address
+        }
+
+        pub fn edit_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock, limits: StakeLimits) {
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            stakable.reward_amount = reward_amount;
+            stakable.lock = lock;
+            stakable.limits = limits;
+        }
+
+        pub fn set_next_period_to_now(&mut self) {
+            self.next_period = Clock::current_time_rounded_to_minutes();
+        }
+
+        pub fn set_pause(&mut self, pause: Pause) {
+            self.pause = pause;
+        }
+
+        // This method irreversibly puts the component in emergency exit mode
+        //
+        // ## INPUT
+        // - none
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method enables emergency exit mode, which cannot be disabled afterwards
+        // - from now on, rewards are frozen and users can withdraw their stake through emergency_withdraw, ignoring locks and the unstake delay
+        pub fn enable_emergency_exit(&mut self) {
+            self.emergency_exit = true;
+        }
+
+        // This method locks staked tokens for voting on a proposal
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `lock_until`: the date until which the tokens are locked
+        // - `id`: the staking ID
+        // - `proposal_id`: the ID of the proposal the tokens are locked for
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether a DAO is controlling the staking
+        // - the method checks whether the proposal is still open
+        // - the method checks whether the staking ID has stake of the stakable token, or stake delegated to it, which stays locked as well
+        // - the method prunes expired locks and locks of closed proposals from the lock registry, and lock windows that ended more than a day ago
+        // - if no lock window is left, a new one starts, otherwise a lock window without locks means the stake is still in its unlocked day
+        // - the method caps the lock at the maximum owner lock duration from the start of the lock window, so fresh proposals cannot extend it
+        // - the method records the lock in the lock registry, under the proposal ID
+        pub fn set_lock(&mut self, address: ResourceAddress, lock_until: Instant, id: NonFungibleLocalId, proposal_id: u64) {
+            assert!(self.dao_controlled, "This functionality is only available if a DAO is controlling the staking.");
+            assert!(self.closed_proposals.get(&proposal_id).is_none(), "Proposal is already closed.");
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.resources.contains_key(&address)
+                    || self.get_delegated_weight(Delegate::Id(id.clone()), address, self.current_period) > dec!(0),
+                "Stakable not found in staking ID."
+            );
+
+            let now = Clock::current_time_rounded_to_minutes();
+            let mut locks = self
+                .lock_registry
+                .get(&id)
+                .map(|locks| locks.clone())
+                .unwrap_or_default();
+
+            locks.retain(|_, dao_lock| {
+                dao_lock.proposals.retain(|proposal_id, locked_until| {
+                    self.closed_proposals.get(proposal_id).is_none()
+                        && !Clock::current_time_is_at_or_after(*locked_until, TimePrecision::Minute)
+                });
+                !dao_lock.proposals.is_empty()
+                    || !Clock::current_time_is_at_or_after(dao_lock.window_end.add_days(1).unwrap(), TimePrecision::Minute)
+            });
+            assert!(
+                !locks.get(&address).is_some_and(|dao_lock| dao_lock.proposals.is_empty()),
+                "Stake needs to be unlocked for a day before it can be locked again."
+            );
+
+            let dao_lock = locks.entry(address).or_insert(DaoLock {
+                window_start: now,
+                window_end: now,
+                proposals: HashMap::new(),
+            });
+            let max_lock_until = dao_lock.window_start.add_days(self.max_owner_lock_duration).unwrap();
+            let lock_until = if lock_until.seconds_since_unix_epoch > max_lock_until.seconds_since_unix_epoch {
+                max_lock_until
+            } else {
+                lock_until
+            };
+
+            dao_lock.proposals.insert(proposal_id, lock_until);
+            if lock_until.seconds_since_unix_epoch > dao_lock.window_end.seconds_since_unix_epoch {
+                dao_lock.window_end = lock_until;
+            }
+
+            self.lock_registry.insert(id, locks);
+        }
+
+        // This method closes a proposal, releasing all locks set for it
+        //
+        // ## INPUT
+        // - `proposal_id`: the ID of the proposal
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method marks the proposal as closed, so its locks are no longer taken into account
+        pub fn close_proposal(&mut self, proposal_id: u64) {
+            self.closed_proposals.insert(proposal_id, ());
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////HELPER METHODS////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        /// This method claims the rewards of a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - the claimed rewards
+        /// 
+        /// ## LOGIC
+        /// - the method checks amount of unclaimed periods, or whether referral rewards can be claimed
+        /// - the method iterates over all staked tokens and calculates the rewards, excluding stake that was still warming up and multiplied by the boost multiplier and the loyalty multiplier reached in that period
+        /// - the method updates the staking ID to the next period, and removes pending stake that has been activated
+        /// - if the staking ID was referred, the referrer accrues a percentage of the rewards, as far as the referral budget allows and as long as the referrer keeps enough stake
+        /// - the method returns the claimed rewards, including accrued referral rewards
+
+        fn claim_rewards(&mut self, id: &NonFungibleLocalId) -> FungibleBucket {
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+
+            let mut claimed_weeks: i64 = self.current_period - id_data.next_period + 1;
+            if claimed_weeks > self.max_claim_delay {
+                claimed_weeks = self.max_claim_delay;
+            }
+
+            assert!(
+                claimed_weeks > 0 || id_data.referral_rewards > dec!(0),
+                "Wait longer to claim your rewards."
+            );
+
+            let mut staking_reward: Decimal = dec!(0);
+
+            self.id_manager
+                .update_non_fungible_data(id, "next_period", self.current_period + 1);
+
+            for (address, stakable_unit) in self.stakes.iter() {
+                for week in 1..(claimed_weeks + 1) {
+                    if stakable_unit
+                        .rewards
+                        .get(&(self.current_period - week))
+                        .is_some()
+                    {
+                        staking_reward += *stakable_unit
+                            .rewards
+                            .get(&(self.current_period - week))
+                            .unwrap()
+                            * id_data
+                                .resources
+                                .get(&address)
+                                .map_or(dec!(0), |resource| {
+                                    active_stake(resource, self.current_period - week)
+                                        * loyalty_multiplier(
+                                            stakable_unit.loyalty.as_ref(),
+                                            self.current_period - week - resource.stake_start,
+                                        )
+                                })
+                            * id_data.boost_multiplier;
+                    }
+                }
+            }
+
+            let mut resource_map = id_data.resources.clone();
+            for resource in resource_map.values_mut() {
+                resource
+                    .pending_stake
+                    .retain(|(activation_period, _)| *activation_period > self.current_period);
+            }
+            self.id_manager
+                .update_non_fungible_data(id, "resources", resource_map);
+
+            if let Some(referrer) = id_data.referrer.as_ref().filter(|referrer| self.is_eligible_referrer(referrer)) {
+                let referral_reward = (staking_reward * self.referral_percentage)
+                    .min(self.referral_vault.amount() - self.referral_rewards_owed);
+
+                if referral_reward > dec!(0) {
+                    let referrer_data: Id = self.id_manager.get_non_fungible_data(referrer);
+                    self.id_manager.update_non_fungible_data(
+                        referrer,
+                        "referral_rewards",
+                        referrer_data.referral_rewards + referral_reward,
+                    );
+                    self.referral_rewards_owed += referral_reward;
+                }
+            }
+
+            let mut rewards = self.reward_vault.take(staking_reward);
+
+            if id_data.referral_rewards > dec!(0) {
+                self.id_manager
+                    .update_non_fungible_data(id, "referral_rewards", dec!(0));
+                self.referral_rewards_owed -= id_data.referral_rewards;
+                rewards.put(self.referral_vault.take(id_data.referral_rewards));
+            }
+
+            rewards
+        }
+
+        /// This method claims the revenue of a staking ID for the periods it has not claimed yet.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - the claimed revenue, one bucket per token
+        /// 
+        /// ## LOGIC
+        /// - for every stakable and every claimable period, limited by the max claim delay, the method calculates the revenue of the staking ID per token
+        /// - the revenue is the revenue per unit of weight multiplied by the active stake of the staking ID, its boost multiplier and the loyalty multiplier reached in that period
+        /// - the method subtracts the revenue from the unclaimed revenue of the period
+        /// - the method takes the revenue from the revenue vaults
+
+        fn claim_revenue(&mut self, id: &NonFungibleLocalId) -> Vec<Bucket> {
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+            let claimed_weeks: i64 = (self.current_period - id_data.next_period + 1).min(self.max_claim_delay);
+            let mut revenue_amounts: HashMap<ResourceAddress, Decimal> = HashMap::new();
+
+            for (address, stakable_unit) in self.stakes.iter_mut() {
+                let resource = match id_data.resources.get(address) {
+                    Some(resource) => resource,
+                    None => continue,
+                };
+                for week in 1..(claimed_weeks + 1) {
+                    let period = self.current_period - week;
+                    if let Some(revenue) = stakable_unit.revenue.get(&period) {
+                        let id_weight = active_stake(resource, period)
+                            * id_data.boost_multiplier
+                            * loyalty_multiplier(stakable_unit.loyalty.as_ref(), period - resource.stake_start);
+                        let mut unclaimed = stakable_unit.unclaimed_revenue.get_mut(&period).unwrap();
+                        for (token, revenue_per_weight) in revenue.iter() {
+                            let amount = *revenue_per_weight * id_weight;
+                            *revenue_amounts.entry(*token).or_insert(dec!(0)) += amount;
+                            if let Some(unclaimed_amount) = unclaimed.get_mut(token) {
+                                *unclaimed_amount = (*unclaimed_amount - amount).max(dec!(0));
+                            }
+                        }
+                    }
+                }
+            }
+
+            revenue_amounts
+                .into_iter()
+                .filter(|(_, amount)| *amount > dec!(0))
+                .map(|(token, amount)| {
+                    let mut vault = self.revenue_vaults.get_mut(&token).unwrap();
+                    let amount = amount.min(vault.amount());
+                    vault.take_advanced(amount, WithdrawStrategy::Rounded(RoundingMode::ToZero))
+                })
+                .collect()
+        }
+
+        /// This method claims the bribes of a staking ID for the periods it has not claimed yet.
+        ///
+        /// ## INPUT
+        /// - `id`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - the claimed bribes, one bucket per bribe token
+        ///
+        /// ## LOGIC
+        /// - for every ended period within the max claim delay the staking ID voted in, the method removes its votes, so they can only be claimed once
+        /// - for every stakable the staking ID voted for, the method takes its share of every bribe, pro-rata to its votes
+        /// - the method merges the claimed bribes per token
+
+        fn claim_bribes(&mut self, id: &NonFungibleLocalId) -> Vec<Bucket> {
+            let mut claimed: HashMap<ResourceAddress, Bucket> = HashMap::new();
+
+            for period in (self.current_period - self.max_claim_delay).max(0)..self.current_period {
+                let id_votes = match self.id_gauge_votes.remove(&(period, id.clone())) {
+                    Some(id_votes) => id_votes,
+                    None => continue,
+                };
+                let total_votes = self.gauge_votes.get(&period).unwrap().clone();
+
+                for (address, votes) in id_votes.iter() {
+                    let total = total_votes.get(address).copied().unwrap_or(dec!(0));
+                    if *votes <= dec!(0) || total <= dec!(0) {
+                        continue;
+                    }
+                    let bribe_ids = match self.period_bribes.get(&(period, *address)) {
+                        Some(bribe_ids) => bribe_ids.clone(),
+                        None => continue,
+                    };
+
+                    for bribe_id in bribe_ids {
+                        let mut bribe = self.bribes.get_mut(&bribe_id).unwrap();
+                        if bribe.amount == dec!(0) {
+                            continue;
+                        }
+                        let share = (bribe.amount * *votes / total).min(bribe.vault.amount());
+                        let bribe_share: Bucket = bribe
+                            .vault
+                            .take_advanced(share, WithdrawStrategy::Rounded(RoundingMode::ToZero))
+                            .into();
+                        match claimed.get_mut(&bribe_share.resource_address()) {
+                            Some(bucket) => bucket.put(bribe_share),
+                            None => {
+                                claimed.insert(bribe_share.resource_address(), bribe_share);
+                            }
+                        }
+                    }
+                }
+            }
+
+            claimed.into_values().collect()
+        }
+
+
+        /// This method multiplies gauge weights by the value of the staked tokens of every stakable.
+        /// 
+        /// ## INPUT
+        /// - `weights`: the gauge weights
+        ///
+        /// ## OUTPUT
+        /// - the value weighted gauge weights, or the original weights if no oracle is set or a price is missing or stale
+        /// 
+        /// ## LOGIC
+        /// - stakables without staked tokens get no weight, without looking up their price
+        /// - the method looks up the price of every other weighted stakable token (or the underlying token of a pool unit) through the price oracle
+        /// - if a price is missing, not positive or older than the maximum price age, value weighting is skipped for this period
+        /// - the method multiplies every weight by the value of the staked tokens
+
+        fn value_weights(&self, weights: HashMap<ResourceAddress, Decimal>) -> HashMap<ResourceAddress, Decimal> {
+            let oracle = match &self.oracle {
+                Some(oracle) => oracle,
+                None => return weights,
+            };
+            let now = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+            let mut value_weights: HashMap<ResourceAddress, Decimal> = HashMap::new();
+
+            for (address, weight) in weights.iter() {
+                let stakable = match self.stakes.get(address) {
+                    Some(stakable) => stakable,
+                    None => continue,
+                };
+                let (priced_token, staked_amount) = staked_value(stakable, stakable.amount_staked);
+                if staked_amount == dec!(0) {
+                    value_weights.insert(*address, dec!(0));
+                    continue;
+                }
+                let (price, updated) = match oracle.get_price(priced_token) {
+                    Some(price) => price,
+                    None => return weights,
+                };
+                if price <= dec!(0) || now - updated.seconds_since_unix_epoch > self.max_price_age * 60 {
+                    return weights;
+                }
+                value_weights.insert(*address, *weight * staked_amount * price);
+            }
+
+            value_weights
+        }
+
+        /// This method stores a bribe for a period and stakable token.
+        /// 
+        /// ## INPUT
+        /// - `bucket`: the bribe
+        /// - `address`: the address of the stakable token
+        /// - `period`: the period
+        /// - `refund_account`: the account unclaimed bribes are returned to, if any
+        /// - `capped`: whether the maximum amount of bribes per period and stakable token applies
+        ///
+        /// ## OUTPUT
+        /// - the ID of the bribe
+        /// 
+        /// ## LOGIC
+        /// - a bribe without refund account is merged into an existing bribe of the same token without refund account, if there is one
+        /// - otherwise, the method checks the maximum amount of bribes if capped, stores the bribe and adds it to the bribes of the period and stakable token
+        /// - rolled forward bribes are merged, so they never take up more than one bribe per token
+
+        fn insert_bribe(
+            &mut self,
+            bucket: FungibleBucket,
+            address: ResourceAddress,
+            period: i64,
+            refund_account: Option<ComponentAddress>,
+            capped: bool,
+        ) -> u64 {
+            let mut bribe_ids = self
+                .period_bribes
+                .get(&(period, address))
+                .map_or(vec![], |bribe_ids| bribe_ids.clone());
+
+            if refund_account.is_none() {
+                for bribe_id in bribe_ids.iter() {
+                    let mut bribe = self.bribes.get_mut(bribe_id).unwrap();
+                    if bribe.refund_account.is_none() && bribe.vault.resource_address() == bucket.resource_address() {
+                        bribe.amount += bucket.amount();
+                        bribe.vault.put(bucket);
+                        return *bribe_id;
+                    }
+                }
+            }
+
+            assert!(
+                !capped || (bribe_ids.len() as u64) < self.max_bribes,
+                "Maximum amount of bribes reached for this period and stakable."
+            );
+
+            self.bribe_counter += 1;
+            self.bribes.insert(
+                self.bribe_counter,
+                Bribe {
+                    address,
+                    period,
+                    amount: bucket.amount(),
+                    vault: FungibleVault::with_bucket(bucket),
+                    refund_account,
+                },
+            );
+
+            bribe_ids.push(self.bribe_counter);
+            self.period_bribes.insert((period, address), bribe_ids);
+
+            self.bribe_counter
+        }
+
+        /// This method renders the display of a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `resources`: the resources staked to the staking ID
+        /// - `boost_multiplier`: the boost multiplier of the staking ID
+        ///
+        /// ## OUTPUT
+        /// - the name of the staking ID
+        /// - the description of the staking ID
+        /// - the key image URL of the staking ID
+        /// 
+        /// ## LOGIC
+        /// - the method calculates the total stake and the highest tier reached by it
+        /// - the method renders the name and description from the stake summary
+        /// - the method renders the key image from the template, or as an SVG data URI
+
+        fn id_display(
+            &self,
+            id: &NonFungibleLocalId,
+            resources: &HashMap<ResourceAddress, Resource>,
+            boost_multiplier: Decimal,
+        ) -> (String, String, Url) {
+            let number: String = match id {
+                NonFungibleLocalId::Integer(number) => number.value().to_string(),
+                _ => id.to_string(),
+            };
+            let total_stake: Decimal = resources
+                .values()
+                .fold(dec!(0), |total, resource| total + resource.amount_staked);
+            let tier: String = self
+                .id_tiers
+                .iter()
+                .filter(|(minimum_stake, _)| total_stake >= *minimum_stake)
+                .max_by(|(a, _), (b, _)| a.cmp(b))
+                .map_or("None".to_string(), |(_, tier)| tier.clone());
+
+            let name = format!("{} Staking ID #{}", self.name, number);
+            let description = format!(
+                "Total stake: {}. Tier: {}. Boost: {}x.",
+                total_stake, tier, boost_multiplier
+            );
+
+            let key_image_url = match &self.id_image {
+                IdImage::Template(template) => template
+                    .replace("{id}", &number)
+                    .replace("{tier}", &tier)
+                    .replace("{stake}", &total_stake.to_string()),
+                IdImage::Svg => {
+                    let svg = format!(
+                        "<svg xmlns='http://www.w3.org/2000/svg' width='500' height='500'>\
+                        <rect width='500' height='500' fill='black'/>\
+                        <text x='250' y='180' fill='white' font-size='32' text-anchor='middle'>{}</text>\
+                        <text x='250' y='260' fill='white' font-size='24' text-anchor='middle'>Total stake: {}</text>\
+                        <text x='250' y='310' fill='white' font-size='24' text-anchor='middle'>Tier: {}</text>\
+                        <text x='250' y='360' fill='white' font-size='24' text-anchor='middle'>Boost: {}x</text>\
+                        </svg>",
+                        name, total_stake, tier, boost_multiplier
+                    );
+                    format!(
+                        "data:image/svg+xml;utf8,{}",
+                        svg.replace('%', "%25")
+                            .replace('#', "%23")
+                            .replace('<', "%3C")
+                            .replace('>', "%3E")
+                    )
+                }
+            };
+
+            (name, description, Url::of(key_image_url))
+        }
+
+        /// This method checks whether a staking ID is eligible to refer other staking IDs.
+        /// 
+        /// ## INPUT
+        /// - `referrer`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - whether the staking ID is eligible
+        /// 
+        /// ## LOGIC
+        /// - the method sums the stake of the staking ID of all stakable tokens at the end of the previous period, so stake needs to have been held for a period
+        /// - the staking ID is eligible if this stake is positive and at least the minimum referrer stake
+
+        fn is_eligible_referrer(&self, referrer: &NonFungibleLocalId) -> bool {
+            let referrer_stake = self.stakes.keys().fold(dec!(0), |total, address| {
+                total + self.get_stake_at(referrer.clone(), *address, self.current_period - 1)
+            });
+
+            referrer_stake > dec!(0) && referrer_stake >= self.min_referrer_stake
+        }
+
+        /// This method determines until when a staking ID's stake is locked by the DAO.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `address`: the address of the stakable token
+        ///
+        /// ## OUTPUT
+        /// - the latest lock of all open proposals, if any
+        /// 
+        /// ## LOGIC
+        /// - the method looks up the locks of the staking ID in the lock registry
+        /// - the method ignores locks of closed proposals
+        /// - the method returns the maximum of the remaining locks
+
+        fn dao_locked_until(&self, id: &NonFungibleLocalId, address: ResourceAddress) -> Option<Instant> {
+            let locks = self.lock_registry.get(id)?;
+            let locked_until = locks
+                .get(&address)?
+                .proposals
+                .iter()
+                .filter(|(proposal_id, _)| self.closed_proposals.get(proposal_id).is_none())
+                .map(|(_, locked_until)| *locked_until)
+                .max_by_key(|locked_until| locked_until.seconds_since_unix_epoch);
+
+            locked_until
+        }
+
+        /// This method checks whether stake is not delegated to a staking ID that is voting with it.
+        /// 
+        /// ## INPUT
+        /// - `delegations`: the delegations of the delegating staking ID
+        /// - `address`: the address of the stakable token
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - if the stake is delegated to a staking ID, the method checks whether that staking ID is not locked by an open proposal, which also locks the delegated stake
+
+        fn check_delegate_unlocked(&self, delegations: &HashMap<ResourceAddress, Delegate>, address: ResourceAddress) {
+            if let Some(Delegate::Id(delegate)) = delegations.get(&address) {
+                if let Some(locked_until) = self.dao_locked_until(delegate, address) {
+                    assert!(
+                        Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute),
+                        "Stake is delegated to a staking ID currently participating in a vote."
+                    );
+                }
+            }
+        }
+
+        /// This method updates the stake delegated to a delegate, and the stake delegated by the delegating staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the delegating staking ID
+        /// - `delegate`: the staking ID or account delegated to
+        /// - `address`: the address of the stakable token
+        /// - `change`: the change in delegated stake
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method updates the checkpoint of the current period of the delegate, or adds one if it does not exist yet
+        /// - the method does the same for the checkpoints of the stake delegated by the staking ID
+
+        fn update_delegated_stake(&mut self, id: &NonFungibleLocalId, delegate: Delegate, address: ResourceAddress, change: Decimal) {
+            let key = (delegate, address);
+            if self.delegated_stake.get(&key).is_none() {
+                self.delegated_stake.insert(key.clone(), Checkpoints::new());
+            }
+            {
+                let mut checkpoints = self.delegated_stake.get_mut(&key).unwrap();
+                let amount = latest_checkpoint(&checkpoints) + change;
+                write_checkpoint(&mut checkpoints, self.current_period, amount);
+            }
+
+            let key = (id.clone(), address);
+            if self.delegated_out.get(&key).is_none() {
+                self.delegated_out.insert(key.clone(), Checkpoints::new());
+            }
+            let mut checkpoints = self.delegated_out.get_mut(&key).unwrap();
+            let amount = latest_checkpoint(&checkpoints) + change;
+            write_checkpoint(&mut checkpoints, self.current_period, amount);
+        }
+
+        /// This method records the stake of a staking ID and the total stake of a stakable token for the current period.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `address`: the address of the stakable token
+        /// - `id_amount_staked`: the new amount of tokens staked to the staking ID
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method writes the new stake of the staking ID to its checkpoints
+        /// - the method writes the total amount staked of the stakable token to its checkpoints
+
+        fn update_stake_checkpoints(&mut self, id: &NonFungibleLocalId, address: ResourceAddress, id_amount_staked: Decimal) {
+            let key = (id.clone(), address);
+            if self.stake_checkpoints.get(&key).is_none() {
+                self.stake_checkpoints.insert(key.clone(), Checkpoints::new());
+            }
+            write_checkpoint(
+                &mut self.stake_checkpoints.get_mut(&key).unwrap(),
+                self.current_period,
+                id_amount_staked,
+            );
+
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            let total_amount_staked = stakable.amount_staked;
+            write_checkpoint(&mut stakable.checkpoints, self.current_period, total_amount_staked);
+        }
+
+        /// This method adds or removes the weight of the stake of a staking ID in a stakable token, for the current period and every future period it changes in.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `resource`: the stake of the staking ID
+        /// - `boost_multiplier`: the boost multiplier of the staking ID
+        /// - `add`: whether the weight is added or removed
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the weight in a period is the active stake in that period, multiplied by the boost multiplier and the loyalty multiplier reached in that period
+        /// - the method adds or removes the weight and active stake of the current period to the weight and active amount of the stakable token
+        /// - the weight changes when pending stake activates and when the stake reaches a step of the loyalty schedule, the method adds or removes these changes to the pending weight and pending amount of those periods
+        /// - the weight is removed before the stake of a staking ID changes and added again after, so the scheduled changes always match the stake
+
+        fn apply_weight(&mut self, address: ResourceAddress, resource: &Resource, boost_multiplier: Decimal, add: bool) {
+            let current_period = self.current_period;
+            let sign = if add { dec!(1) } else { dec!(-1) };
+            let loyalty = self.stakes.get(&address).unwrap().loyalty.clone();
+            let stake_start = resource.stake_start;
+            let weight_at = |period: i64| {
+                active_stake(resource, period)
+                    * boost_multiplier
+                    * loyalty_multiplier(loyalty.as_ref(), period - stake_start)
+            };
+
+            let mut change_periods: Vec<i64> = resource
+                .pending_stake
+                .iter()
+                .map(|(activation_period, _)| *activation_period)
+                .chain(
+                    loyalty
+                        .iter()
+                        .flat_map(|loyalty| loyalty.schedule.iter().map(move |(periods, _)| stake_start + *periods)),
+                )
+                .filter(|period| *period > current_period)
+                .collect();
+            change_periods.sort();
+            change_periods.dedup();
+
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            stakable.weight += sign * weight_at(current_period);
+            stakable.active_amount += sign * active_stake(resource, current_period);
+
+            for period in change_periods {
+                let change = weight_at(period) - weight_at(period - 1);
+                if change != dec!(0) {
+                    let pending_weight = stakable
+                        .pending_weight
+                        .get(&period)
+                        .map_or(dec!(0), |pending_weight| *pending_weight);
+                    stakable.pending_weight.insert(period, pending_weight + sign * change);
+                }
+            }
+            for (activation_period, pending) in resource.pending_stake.iter() {
+                if *activation_period > current_period {
+                    let pending_amount = stakable
+                        .pending_amount
+                        .get(activation_period)
+                        .map_or(dec!(0), |pending_amount| *pending_amount);
+                    stakable.pending_amount.insert(*activation_period, pending_amount + sign * *pending);
+                }
+            }
+        }
+
+        /// This method updates the bonded boost NFTs of a staking ID and applies its new boost multiplier.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `id_data`: the data of the staking ID
+        /// - `boost_nfts`: the boost NFTs bonded to the staking ID
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method calculates the new boost multiplier from the boost table
+        /// - the method replaces the weight of the staking ID in all stakable tokens it stakes by its weight with the new multiplier
+        /// - the method records the boost NFTs and multiplier on the staking ID
+
+        fn update_boost(&mut self, id: &NonFungibleLocalId, id_data: &Id, boost_nfts: Vec<NonFungibleLocalId>) {
+            let boost = self.boost.as_ref().unwrap();
+            let multiplier = match &boost.table {
+                BoostTable::Count(table) => table
+                    .iter()
+                    .filter(|(count, _)| *count <= boost_nfts.len() as u64)
+                    .map(|(_, multiplier)| *multiplier)
+                    .fold(dec!(1), |highest, multiplier| if multiplier > highest { multiplier } else { highest }),
+                BoostTable::PerNft(table) => boost_nfts
+                    .iter()
+                    .filter_map(|boost_nft| table.get(boost_nft))
+                    .fold(dec!(1), |highest, multiplier| if *multiplier > highest { *multiplier } else { highest }),
+            };
+
+            for (address, resource) in id_data.resources.iter() {
+                self.apply_weight(*address, resource, id_data.boost_multiplier, false);
+                self.apply_weight(*address, resource, multiplier, true);
+            }
+
+            self.id_manager
+                .update_non_fungible_data(id, "boost_nfts", boost_nfts);
+            self.id_manager
+                .update_non_fungible_data(id, "boost_multiplier", multiplier);
+        }
+
+        /// This method checks the stake limits of a stakable token for an added amount of stake.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `resources`: the resources of the staking ID before staking
+        /// - `amount`: the added amount of the stakable token, directly or as leg of a basket
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method adds the leg tokens held for every basket the token is a leg of to the total stake and the stake of the staking ID
+        /// - the method checks whether the total stake and the stake of the staking ID, including the added amount, stay within the limits
+
+        fn check_limits(&self, address: ResourceAddress, resources: &HashMap<ResourceAddress, Resource>, amount: Decimal) {
+            let stakable = self.stakes.get(&address).unwrap();
+            let mut total_staked = stakable.amount_staked + amount;
+            let mut id_staked = resources.get(&address).map_or(dec!(0), |resource| resource.amount_staked) + amount;
+            for (basket, basket_unit) in self.stakes.iter() {
+                if let Some(ratio) = basket_unit.basket.as_ref().and_then(|legs| legs.get(&address)) {
+                    total_staked += basket_unit.amount_staked * *ratio;
+                    id_staked += resources.get(basket).map_or(dec!(0), |resource| resource.amount_staked) * *ratio;
+                }
+            }
+
+            if let Some(max_total_stake) = stakable.limits.max_total_stake {
+                assert!(total_staked <= max_total_stake, "Stake exceeds the pool capacity.");
+            }
+            if let Some(max_stake_per_id) = stakable.limits.max_stake_per_id {
+                assert!(id_staked <= max_stake_per_id, "Stake exceeds the maximum stake per staking ID.");
+            }
+        }
+
+        /// This method checks whether a staker is allowed to stake a stakable token.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `access_proofs`: the proofs supplied by the staker
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - if staking is public, the method does nothing
+        /// - if a badge is required, the method checks whether a proof of that badge is supplied
+        /// - if an allowlist is used, the method checks whether one of the Merkle proofs leads from its account to the root
+        /// - if an allowlist is used, the method checks whether the transaction is signed by an owner key of that account
+
+        fn check_access(&self, address: ResourceAddress, access_proofs: &[AccessProof]) {
+            match &self.stakes.get(&address).unwrap().access {
+                StakeAccess::Public => {}
+                StakeAccess::Badge(badge) => {
+                    assert!(
+                        access_proofs.iter().any(|access_proof| {
+                            matches!(access_proof, AccessProof::Badge(proof) if proof.resource_address() == *badge)
+                        }),
+                        "Staking this token requires a valid access proof."
+                    );
+                }
+                StakeAccess::Allowlist(root) => {
+                    let account = access_proofs
+                        .iter()
+                        .find_map(|access_proof| match access_proof {
+                            AccessProof::Allowlist(account, merkle_proof) => {
+                                let computed_root = merkle_proof.iter().fold(
+                                    hash(account.as_node_id().0),
+                                    |node, sibling| {
+                                        if node.0 <= sibling.0 {
+                                            hash([node.0, sibling.0].concat())
+                                        } else {
+                                            hash([sibling.0, node.0].concat())
+                                        }
+                                    },
+                                );
+                                (computed_root == *root).then_some(*account)
+                            }
+                            AccessProof::Badge(_) => None,
+                        })
+                        .expect("Account is not on the allowlist.");
+
+                    let account: Global<Account> = Global::from(account);
+                    let owner_keys: Vec<PublicKeyHash> = account
+                        .get_metadata("owner_keys")
+                        .unwrap()
+                        .expect("Account has no owner keys.");
+                    let owner_badges: Vec<NonFungibleGlobalId> = owner_keys
+                        .into_iter()
+                        .map(NonFungibleGlobalId::from_public_key_hash)
+                        .collect();
+                    Runtime::assert_access_rule(rule!(require_any_of(owner_badges)));
+                }
+            }
+        }
+
+        /// This method counts the staked tokens and puts them away in the staking component's vault.
+        /// 
+        /// ## INPUT
+        /// - `stake_bucket`: the bucket of staked tokens
+        ///
+        /// ## OUTPUT
+        /// - the amount of staked tokens
+        /// - the address of the stakable token
+        /// - the staked NFTs with their weight, if the stakable is an NFT collection
+        /// 
+        /// ## LOGIC
+        /// - the method checks whether the staked token is a stakable token
+        /// - for NFTs, the method determines the weight of every NFT, and the staked amount is their total weight
+        /// - the method puts the staked tokens in the staking component's vault
+        /// - the method returns the amount of staked tokens, the address of the stakable token and the staked NFTs
+
+        fn stake_tokens(&mut self, stake_bucket: Bucket) -> (Decimal, ResourceAddress, HashMap<NonFungibleLocalId, Decimal>) {   
+            let address: ResourceAddress = stake_bucket.resource_address();
+            assert!(self.stakes.get(&address).is_some(), "Token supplied does not match requested stakable token.");
+            let mut staked_nfts: HashMap<NonFungibleLocalId, Decimal> = HashMap::new();
+            let stake_amount: Decimal = if address.is_fungible() {
+                stake_bucket.amount()
+            } else {
+                for nft in stake_bucket.as_non_fungible().non_fungible_local_ids() {
+                    let weight = self.nft_weight(address, &nft);
+                    staked_nfts.insert(nft, weight);
+                }
+                staked_nfts.values().fold(dec!(0), |total, weight| total + *weight)
+            };
+            self.stakes
+                .get_mut(&address)
+                .unwrap()
+                .vault
+                .put(stake_bucket);
+
+            (stake_amount, address, staked_nfts)
+        }
+
+        /// This method checks and burns an unstake receipt.
+        /// 
+        /// ## INPUT
+        /// - `receipt`: the unstake receipt
+        ///
+        /// ## OUTPUT
+        /// - the data of the unstake receipt
+        /// 
+        /// ## LOGIC
+        /// - the method checks whether unstaking is paused
+        /// - if the receipt is a stake transfer receipt, the method checks the component is in emergency exit mode, burns the receipt and returns its stake as immediately redeemable
+        /// - the method checks the receipt
+        /// - the method checks the redemption time, unless the component is in emergency exit mode
+        /// - the method burns the receipt
+
+        fn redeem_unstake_receipt(&mut self, receipt: Bucket) -> UnstakeReceipt {
+            assert!(!self.pause.unstake, "Unstaking is currently paused.");
+
+            if receipt.resource_address() == self.stake_transfer_receipt_manager.address() {
+                assert!(
+                    self.emergency_exit,
+                    "Stake transfer receipts can only be redeemed in emergency exit mode."
+                );
+                let transfer_data = receipt
+                    .as_non_fungible()
+                    .non_fungible::<StakeTransferReceipt>()
+                    .data();
+                receipt.burn();
+
+                return UnstakeReceipt {
+                    address: transfer_data.address,
+                    amount: transfer_data.amount,
+                    redemption_time: Clock::current_time_rounded_to_minutes(),
+                    nfts: vec![],
+                };
+            }
+
+            assert!(receipt.resource_address() == self.unstake_receipt_manager.address());
+
+            let receipt_data = receipt
+                .as_non_fungible()
+                .non_fungible::<UnstakeReceipt>()
+                .data();
+
+            if !self.emergency_exit {
+                assert!(
+                    Clock::current_time_is_at_or_after(
+                        receipt_data.redemption_time,
+                        TimePrecision::Minute
+                    ),
+                    "You cannot unstake tokens before the redemption time."
+                );
+            }
+
+            receipt.burn();
+
+            receipt_data
+        }
+
+        /// This method recalls a soulbound staking ID from the vault holding it.
+        /// 
+        /// ## INPUT
+        /// - `vault`: the address of the vault holding the staking ID
+        /// - `id`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - a bucket with the staking ID
+        /// 
+        /// ## LOGIC
+        /// - the method calls the recall method of the vault, which fails if the vault does not hold the staking ID, or if this component does not have the recaller role of its resource
+        /// - the method checks whether the recalled bucket holds staking IDs
+
+        fn recall_id(&self, vault: InternalAddress, id: NonFungibleLocalId) -> NonFungibleBucket {
+            let id_bucket: Bucket = scrypto_decode(&ScryptoVmV1Api::object_call_direct(
+                vault.as_node_id(),
+                NON_FUNGIBLE_VAULT_RECALL_NON_FUNGIBLES_IDENT,
+                scrypto_args!(indexset!(id)),
+            ))
+            .unwrap();
+            assert!(
+                id_bucket.resource_address() == self.id_manager.address(),
+                "Vault does not hold staking IDs."
+            );
+
+            id_bucket.as_non_fungible()
+        }
+
+        /// This method releases the leg tokens of basket units.
+        /// 
+        /// ## INPUT
+        /// - `basket`: the address of the basket
+        /// - `units`: the amount of basket units
+        ///
+        /// ## OUTPUT
+        /// - the leg tokens, one bucket per leg
+        /// 
+        /// ## LOGIC
+        /// - the method burns the basket units
+        /// - the method takes the amount of every leg token required for the basket units from the vault of that leg, rounded down
+
+        fn release_basket(&mut self, basket: ResourceAddress, units: Decimal) -> Vec<Bucket> {
+            let stakable = self.stakes.get_mut(&basket).unwrap();
+            let legs = stakable.basket.clone().unwrap();
+            stakable.vault.take(units).burn();
+
+            legs.iter()
+                .map(|(leg, ratio)| {
+                    self.stakes
+                        .get_mut(leg)
+                        .unwrap()
+                        .vault
+                        .take_advanced(units * *ratio, WithdrawStrategy::Rounded(RoundingMode::ToZero))
+                })
+                .collect()
+        }
+
+        /// This method determines the weight of an NFT of an NFT stakable.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the NFT stakable
+        /// - `nft`: the local ID of the NFT
+        ///
+        /// ## OUTPUT
+        /// - the weight of the NFT
+        /// 
+        /// ## LOGIC
+        /// - for a weight table, the method looks up the weight of the NFT, or uses the default weight
+        /// - for a data field, the method reads the NFT data and takes the numeric field at the set index
+        /// - the method checks whether the weight is positive
+
+        fn nft_weight(&self, address: ResourceAddress, nft: &NonFungibleLocalId) -> Decimal {
+            let weight = match self
+                .stakes
+                .get(&address)
+                .unwrap()
+                .nft_weight
+                .as_ref()
+                .expect("NFT weights are not set for this stakable.")
+            {
+                NftWeight::Table(weights, default_weight) => weights.get(nft).copied().unwrap_or(*default_weight),
+                NftWeight::Field(index) => {
+                    let data: ScryptoValue = scrypto_decode(&ScryptoVmV1Api::object_call(
+                        address.as_node_id(),
+                        NON_FUNGIBLE_RESOURCE_MANAGER_GET_NON_FUNGIBLE_IDENT,
+                        scrypto_args!(nft.clone()),
+                    ))
+                    .unwrap();
+                    let field = match &data {
+                        ScryptoValue::Tuple { fields } => fields.get(*index as usize),
+                        _ => None,
+                    };
+                    match field {
+                        Some(ScryptoValue::Custom { value: ScryptoCustomValue::Decimal(weight) }) => *weight,
+                        Some(ScryptoValue::U8 { value }) => Decimal::from(*value),
+                        Some(ScryptoValue::U16 { value }) => Decimal::from(*value),
+                        Some(ScryptoValue::U32 { value }) => Decimal::from(*value),
+                        Some(ScryptoValue::U64 { value }) => Decimal::from(*value),
+                        _ => panic!("NFT weight field is not a number."),
+                    }
+                }
+            };
+            assert!(weight > dec!(0), "NFT has no weight.");
 
-            (stake_amount, address)
+            weight
         }
 
         /// This method counts the staked tokens from a transfer receipt and burns it.
@@ -0,0 +1,69 @@
+/*!
+This module defines the price oracle interface used by the staking component, and a simple owner-fed price feed implementing it.
+
+Any component can be used as an oracle, as long as it has a `get_price` method with the following signature:
+- `get_price(address: ResourceAddress) -> Option<(Decimal, Instant)>`, returning the price of a token in a common unit of account (e.g. USD) and the time the price was last updated, or None if the token has no price.
+
+The staking component stores the oracle as a `Global<AnyComponent>` and calls it through the `PriceOracle` trait, so oracles of any blueprint can be plugged in.
+The price feed blueprint is a stand-in oracle for testing, of which the owner sets the prices manually.
+*/
+
+use scrypto::prelude::*;
+
+// Price oracle interface, implemented for any component with a get_price method.
+pub trait PriceOracle {
+    fn get_price(&self, address: ResourceAddress) -> Option<(Decimal, Instant)>;
+}
+
+impl PriceOracle for Global<AnyComponent> {
+    fn get_price(&self, address: ResourceAddress) -> Option<(Decimal, Instant)> {
+        self.call_raw::<Option<(Decimal, Instant)>>("get_price", scrypto_args!(address))
+    }
+}
+
+#[blueprint]
+mod price_feed {
+    enable_method_auth! {
+        methods {
+            get_price => PUBLIC;
+            set_price => restrict_to: [OWNER];
+        }
+    }
+
+    struct PriceFeed {
+        // keyvaluestore, holding the price of every token and the time it was last updated
+        prices: KeyValueStore<ResourceAddress, (Decimal, Instant)>,
+    }
+
+    impl PriceFeed {
+        // this function instantiates the price feed component
+        //
+        // ## INPUT
+        // - `controller`: the address of the controller badge, which will be the owner of the price feed component
+        //
+        // ## OUTPUT
+        // - the price feed component
+        //
+        // ## LOGIC
+        // - the price feed component is instantiated
+        pub fn new(controller: ResourceAddress) -> Global<PriceFeed> {
+            Self {
+                prices: KeyValueStore::new(),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::Fixed(rule!(require(controller))))
+            .globalize()
+        }
+
+        // This method returns the price of a token and the time it was last updated, or None if no price was set
+        pub fn get_price(&self, address: ResourceAddress) -> Option<(Decimal, Instant)> {
+            self.prices.get(&address).map(|price| *price)
+        }
+
+        pub fn set_price(&mut self, address: ResourceAddress, price: Decimal) {
+            assert!(price > dec!(0), "Price needs to be positive.");
+            self.prices
+                .insert(address, (price, Clock::current_time_rounded_to_minutes()));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+/*!
+This blueprint enables a secondary market for staked tokens, on top of the stake transfer receipts of the staking component.
+
+A seller lists a stake transfer receipt for a price per staked token, in any fungible token. The seller receives an order badge, which is used to collect the proceeds and cancel the order.
+Buyers can buy any part of a listed receipt. If only part of it is bought, the receipt is split by the staking component, and the remaining part stays listed.
+The bought stake transfer receipt can be settled straight into the buyer's own staking ID by passing it to the stake method of the staking component in the same transaction.
+*/
+
+use crate::staking::Staking;
+use crate::StakeTransferReceipt;
+use scrypto::prelude::*;
+
+// Order badge structure, minted when a stake transfer receipt is listed, used to collect proceeds and cancel the order.
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct OrderBadge {
+    pub address: ResourceAddress,
+    pub price_address: ResourceAddress,
+    pub price: Decimal,
+}
+
+// Order structure, holding the listed stake transfer receipt and the proceeds of the order.
+#[derive(ScryptoSbor)]
+pub struct Order {
+    pub address: ResourceAddress,
+    pub amount: Decimal,
+    pub price_address: ResourceAddress,
+    pub price: Decimal,
+    pub receipt: NonFungibleVault,
+    pub proceeds: Vault,
+}
+
+#[blueprint]
+mod order_book {
+    enable_method_auth! {
+        methods {
+            list => PUBLIC;
+            buy => PUBLIC;
+            collect => PUBLIC;
+            cancel => PUBLIC;
+            get_order => PUBLIC;
+        }
+    }
+
+    struct OrderBook {
+        // the staking component whose stake transfer receipts are traded
+        staking: Global<Staking>,
+        // address of the stake transfer receipts of the staking component
+        receipt_address: ResourceAddress,
+        // resource manager of the order badges
+        order_badge_manager: ResourceManager,
+        // counter for the orders
+        order_counter: u64,
+        // keyvaluestore, holding all orders
+        orders: KeyValueStore<u64, Order>,
+    }
+
+    impl OrderBook {
+        // this function instantiates the order book component
+        //
+        // ## INPUT
+        // - `staking`: the staking component whose stake transfer receipts are traded
+        // - `name`: the name of your project
+        //
+        // ## OUTPUT
+        // - the order book component
+        //
+        // ## LOGIC
+        // - the address of the stake transfer receipts is read from the staking component
+        // - the order badge resource manager is created
+        // - the order book component is instantiated
+        pub fn new(
+            staking: Global<Staking>,
+            name: String,
+        ) -> Global<OrderBook> {
+            let receipt_address = staking.get_transfer_receipt_address();
+            let (address_reservation, component_address) =
+                Runtime::allocate_component_address(OrderBook::blueprint_id());
+
+            let order_badge_manager = ResourceBuilder::new_integer_non_fungible::<OrderBadge>(OwnerRole::None)
+                .metadata(metadata!(
+                    init {
+                        "name" => format!("{} Stake Order Badge", name), locked;
+                        "description" => format!("A badge of an order of staked tokens in the {} ecosystem.", name), locked;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
+            Self {
+                staking,
+                receipt_address,
+                order_badge_manager,
+                order_counter: 0,
+                orders: KeyValueStore::new(),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .with_address(address_reservation)
+            .globalize()
+        }
+
+        // This method lists a stake transfer receipt for sale
+        //
+        // ## INPUT
+        // - `receipt`: the stake transfer receipt
+        // - `price_address`: the address of the fungible token the receipt is sold for
+        // - `price`: the price per staked token
+        //
+        // ## OUTPUT
+        // - the order badge
+        //
+        // ## LOGIC
+        // - the method checks the receipt, the price token and the price
+        // - the method stores the order, holding the receipt
+        // - the method mints and returns the order badge
+        pub fn list(&mut self, receipt: NonFungibleBucket, price_address: ResourceAddress, price: Decimal) -> Bucket {
+            assert!(
+                receipt.resource_address() == self.receipt_address && receipt.amount() == dec!(1),
+                "Invalid stake transfer receipt supplied!"
+            );
+            assert!(
+                ResourceManager::from(price_address).resource_type().is_fungible(),
+                "Price token needs to be fungible."
+            );
+            assert!(price > dec!(0), "Price needs to be positive.");
+
+            let receipt_data = receipt.non_fungible::<StakeTransferReceipt>().data();
+
+            self.order_counter += 1;
+            self.orders.insert(
+                self.order_counter,
+                Order {
+                    address: receipt_data.address,
+                    amount: receipt_data.amount,
+                    price_address,
+                    price,
+                    receipt: NonFungibleVault::with_bucket(receipt),
+                    proceeds: Vault::new(price_address),
+                },
+            );
+
+            self.order_badge_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.order_counter),
+                OrderBadge {
+                    address: receipt_data.address,
+                    price_address,
+                    price,
+                },
+            )
+        }
+
+        // This method buys (part of) a listed stake transfer receipt
+        //
+        // ## INPUT
+        // - `order_id`: the ID of the order
+        // - `amount`: the amount of staked tokens to buy
+        // - `payment`: the payment, in the price token of the order
+        //
+        // ## OUTPUT
+        // - a stake transfer receipt of the bought amount
+        // - the change of the payment
+        //
+        // ## LOGIC
+        // - the method checks the order, the amount and the payment
+        // - the method takes the price of the bought amount from the payment, rounded up, and stores it as proceeds
+        // - if the full remaining amount is bought, the listed receipt is returned
+        // - otherwise, the listed receipt is split by the staking component, and the remaining part stays listed
+        pub fn buy(&mut self, order_id: u64, amount: Decimal, mut payment: Bucket) -> (Bucket, Bucket) {
+            let receipt: NonFungibleBucket;
+            let remaining_amount: Decimal;
+            {
+                let mut order = self.orders.get_mut(&order_id).expect("Order not found.");
+                assert!(order.amount > dec!(0), "Order is already filled or cancelled.");
+                assert!(amount > dec!(0) && amount <= order.amount, "Invalid amount requested.");
+                assert!(
+                    payment.resource_address() == order.price_address,
+                    "Payment token does not match the price token of the order."
+                );
+
+                let cost = payment.take_advanced(
+                    amount * order.price,
+                    WithdrawStrategy::Rounded(RoundingMode::ToPositiveInfinity),
+                );
+                order.proceeds.put(cost);
+
+                receipt = order.receipt.take_all();
+                order.amount -= amount;
+                remaining_amount = order.amount;
+            }
+
+            if remaining_amount == dec!(0) {
+                return (receipt.into(), payment);
+            }
+
+            let (bought_receipt, remaining_receipt) = self.staking.split_transfer_receipt(receipt, amount);
+            self.orders
+                .get_mut(&order_id)
+                .unwrap()
+                .receipt
+                .put(remaining_receipt.as_non_fungible());
+
+            (bought_receipt, payment)
+        }
+
+        // This method collects the proceeds of an order
+        //
+        // ## INPUT
+        // - `order_proof`: the proof of the order badge
+        //
+        // ## OUTPUT
+        // - the proceeds of the order
+        //
+        // ## LOGIC
+        // - the method checks the order badge
+        // - the method returns the proceeds of the order
+        pub fn collect(&mut self, order_proof: NonFungibleProof) -> Bucket {
+            let order_proof = order_proof
+                .check_with_message(self.order_badge_manager.address(), "Invalid order badge supplied!");
+            let order_id = match order_proof.non_fungible::<OrderBadge>().local_id() {
+                NonFungibleLocalId::Integer(order_id) => order_id.value(),
+                _ => panic!("Invalid order badge supplied!"),
+            };
+
+            self.orders
+                .get_mut(&order_id)
+                .unwrap()
+                .proceeds
+                .take_all()
+        }
+
+        // This method cancels an order
+        //
+        // ## INPUT
+        // - `order_badge`: the order badge
+        //
+        // ## OUTPUT
+        // - the remaining stake transfer receipt, if the order was not filled completely
+        // - the proceeds of the order
+        //
+        // ## LOGIC
+        // - the method checks and burns the order badge
+        // - the method closes the order
+        // - the method returns the remaining receipt and the proceeds
+        pub fn cancel(&mut self, order_badge: NonFungibleBucket) -> (Option<Bucket>, Bucket) {
+            assert!(
+                order_badge.resource_address() == self.order_badge_manager.address()
+                    && order_badge.amount() == dec!(1),
+                "Invalid order badge supplied!"
+            );
+            let order_id = match order_badge.non_fungible::<OrderBadge>().local_id() {
+                NonFungibleLocalId::Integer(order_id) => order_id.value(),
+                _ => panic!("Invalid order badge supplied!"),
+            };
+            order_badge.burn();
+
+            let mut order = self.orders.get_mut(&order_id).unwrap();
+            let receipt: Option<Bucket> = if order.amount > dec!(0) {
+                order.amount = dec!(0);
+                Some(order.receipt.take_all().into())
+            } else {
+                None
+            };
+
+            (receipt, order.proceeds.take_all())
+        }
+
+        // This method returns the staked token, remaining amount, price token and price per staked token of an order
+        pub fn get_order(&self, order_id: u64) -> (ResourceAddress, Decimal, ResourceAddress, Decimal) {
+            let order = self.orders.get(&order_id).expect("Order not found.");
+
+            (order.address, order.amount, order.price_address, order.price)
+        }
+    }
+}
@@ -3,66 +3,2249 @@ use scrypto::this_package;
 use scrypto_test::prelude::*;
 use scrypto_unit::*;
 
+use staker_package::governance::test_bindings::*;
+use staker_package::governance::{ProposalAction, ProposalStatus};
+use staker_package::oracle::test_bindings::*;
+use staker_package::order_book::test_bindings::*;
 use staker_package::test_bindings::*;
+use staker_package::{
+    AccessProof, BoostTable, Delegate, Gauge, Id, IdImage, Lock, Loyalty, NftWeight, Pause, PayoutDestination, PoolUnit, StakeAccess,
+    StakeLimits, TargetApr,
+};
 
-#[test]
-fn test_hello() {
-    // Setup the environment
-    let mut test_runner = TestRunnerBuilder::new().build();
+// Test setup, holding a staking component with two stakable tokens that each distribute 100 reward tokens every period.
+struct Setup {
+    env: TestEnvironment,
+    package_address: PackageAddress,
+    staking: Staking,
+    controller: Bucket,
+    stake_tokens: Bucket,
+    other_tokens: Bucket,
+    reward_tokens: Bucket,
+}
 
-    // Create an account
-    let (public_key, _private_key, account) = test_runner.new_allocated_account();
+impl Setup {
+    fn new() -> Result<Self, RuntimeError> {
+        Self::with_options(false, 30)
+    }
 
-    // Publish package
-    let package_address = test_runner.compile_and_publish(this_package!());
+    fn with_options(dao_controlled: bool, max_owner_lock_duration: i64) -> Result<Self, RuntimeError> {
+        let mut env = TestEnvironment::new();
+        env.disable_auth_module();
+        let package_address = Package::compile_and_publish(this_package!(), &mut env)?;
 
-    // Test the `instantiate_hello` function.
-    let manifest = ManifestBuilder::new()
-        .call_function(
+        let controller: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+            .mint_initial_supply(dec!(2), &mut env)?
+            .into();
+        let mut reward_tokens: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+            .mint_initial_supply(dec!(1_000_000), &mut env)?
+            .into();
+        let stake_tokens: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+            .mint_initial_supply(dec!(1_000_000), &mut env)?
+            .into();
+        let other_tokens: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+            .mint_initial_supply(dec!(1_000_000), &mut env)?
+            .into();
+        let controller_address = controller.resource_address(&mut env)?;
+
+        let rewards = reward_tokens.take(dec!(10_000), &mut env)?;
+        let staking = Staking::new(
+            controller_address,
+            FungibleBucket(rewards),
+            7,
+            "Test".to_string(),
+            "TST".to_string(),
+            dao_controlled,
+            30,
+            max_owner_lock_duration,
+            false,
+            controller_address,
             package_address,
-            "Hello",
-            "instantiate_hello",
-            manifest_args!(),
+            &mut env,
+        )?;
+
+        let mut setup = Self {
+            env,
+            package_address,
+            staking,
+            controller,
+            stake_tokens,
+            other_tokens,
+            reward_tokens,
+        };
+        let stake_address = setup.stake_address();
+        let other_address = setup.other_address();
+        setup.add_stakable(stake_address, None)?;
+        setup.add_stakable(other_address, None)?;
+        Ok(setup)
+    }
+
+    fn stake_address(&mut self) -> ResourceAddress {
+        self.stake_tokens.resource_address(&mut self.env).unwrap()
+    }
+
+    fn other_address(&mut self) -> ResourceAddress {
+        self.other_tokens.resource_address(&mut self.env).unwrap()
+    }
+
+    // Adds a stakable without lock or limits, distributing 100 reward tokens every period.
+    fn add_stakable(&mut self, address: ResourceAddress, pool_unit: Option<PoolUnit>) -> Result<(), RuntimeError> {
+        self.staking.add_stakable(
+            address,
+            dec!(100),
+            Lock {
+                payment: dec!(0),
+                duration: 0,
+            },
+            no_limits(),
+            false,
+            pool_unit,
+            &mut self.env,
         )
-        .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    }
+
+    // Sets the stake limits of a stakable, keeping its reward of 100 tokens per period and no lock.
+    fn set_limits(&mut self, address: ResourceAddress, limits: StakeLimits) -> Result<(), RuntimeError> {
+        self.staking.edit_stakable(
+            address,
+            dec!(100),
+            Lock {
+                payment: dec!(0),
+                duration: 0,
+            },
+            limits,
+            &mut self.env,
+        )
+    }
+
+    // Stakes a bucket of any stakable to a new staking ID, returning the staking ID.
+    fn stake_bucket(&mut self, stake: Bucket) -> Result<Bucket, RuntimeError> {
+        let id = self.staking.stake(stake, None, vec![], None, None, &mut self.env)?;
+        Ok(id.unwrap())
+    }
+
+    // Stakes an amount of the stakable token to a new staking ID, returning the staking ID.
+    fn stake_new(&mut self, amount: Decimal) -> Result<Bucket, RuntimeError> {
+        let stake = self.stake_tokens.take(amount, &mut self.env)?;
+        self.stake_bucket(stake)
+    }
+
+    // Stakes an amount of the other stakable token to a new staking ID, returning the staking ID.
+    fn stake_other_new(&mut self, amount: Decimal) -> Result<Bucket, RuntimeError> {
+        let stake = self.other_tokens.take(amount, &mut self.env)?;
+        self.stake_bucket(stake)
+    }
+
+    // Stakes an amount of the stakable token to an existing staking ID.
+    fn stake(&mut self, id: &Bucket, amount: Decimal) -> Result<(), RuntimeError> {
+        let stake = self.stake_tokens.take(amount, &mut self.env)?;
+        let proof = id.create_proof_of_all(&mut self.env)?;
+        self.staking.stake(stake, Some(proof), vec![], None, None, &mut self.env)?;
+        Ok(())
+    }
+
+    fn id_proof(&mut self, id: &Bucket) -> Result<NonFungibleProof, RuntimeError> {
+        Ok(NonFungibleProof(id.create_proof_of_all(&mut self.env)?))
+    }
+
+    fn unstake(&mut self, id: &Bucket, amount: Decimal) -> Result<Bucket, RuntimeError> {
+        let stake_address = self.stake_address();
+        let proof = self.id_proof(id)?;
+        self.staking
+            .start_unstake(proof, stake_address, amount, false, None, None, &mut self.env)
+    }
+
+    // Claims the rewards of a staking ID, returning the claimed amount.
+    fn claim(&mut self, id: &Bucket) -> Result<Decimal, RuntimeError> {
+        let proof = self.id_proof(id)?;
+        let (rewards, _payouts) = self.staking.update_id(proof, &mut self.env)?;
+        rewards.amount(&mut self.env)
+    }
+
+    // Moves the clock forward by a number of days, and updates the period of the staking component.
+    fn advance_days(&mut self, days: i64) -> Result<(), RuntimeError> {
+        let now = self.env.get_current_time();
+        self.env.set_current_time(now.add_days(days).unwrap());
+        self.staking.update_period(&mut self.env)
+    }
+
+    // Instantiates a governance component controlling the staking component, with a 7 day voting period, in which every staked token is one vote.
+    fn governance(&mut self, id_address: ResourceAddress, proposal_threshold: Decimal) -> Result<Governance, RuntimeError> {
+        let controller_address = self.controller.resource_address(&mut self.env)?;
+        let staking_controller = self.controller.take(dec!(1), &mut self.env)?;
+        let stake_address = self.stake_address();
+
+        Governance::new(
+            controller_address,
+            self.staking,
+            staking_controller,
+            id_address,
+            7,
+            dec!(100),
+            proposal_threshold,
+            HashMap::from([(stake_address, dec!(1))]),
+            self.package_address,
+            &mut self.env,
+        )
+    }
+
+    fn advance_periods(&mut self, periods: i64) -> Result<(), RuntimeError> {
+        for _ in 0..periods {
+            self.advance_days(7)?;
+        }
+        Ok(())
+    }
+
+    // Sets a gauge emitting 100 reward tokens every period, split over the stakables by the given weights.
+    fn set_gauge(
+        &mut self,
+        weights: HashMap<ResourceAddress, Decimal>,
+        vote_resource: Option<ResourceAddress>,
+        value_weighted: bool,
+    ) -> Result<(), RuntimeError> {
+        self.staking.set_gauge(
+            Some(Gauge {
+                emission: dec!(100),
+                weights,
+                vote_resource,
+                value_weighted,
+            }),
+            &mut self.env,
+        )
+    }
+
+    // Instantiates a price feed without prices, and sets it as the oracle of the staking component.
+    fn price_feed(&mut self) -> Result<PriceFeed, RuntimeError> {
+        let controller_address = self.controller.resource_address(&mut self.env)?;
+        let price_feed = PriceFeed::new(controller_address, self.package_address, &mut self.env)?;
+        self.staking.set_oracle(
+            Some(ComponentAddress::try_from(price_feed.0.as_bytes()).unwrap()),
+            60 * 24 * 30,
+            &mut self.env,
+        )?;
+        Ok(price_feed)
+    }
+
+    // Turns the stakable token into a revenue stakable, accepting the reward token as revenue.
+    fn accept_revenue(&mut self) -> Result<(), RuntimeError> {
+        let stake_address = self.stake_address();
+        let revenue_address = self.reward_tokens.resource_address(&mut self.env)?;
+        self.staking
+            .set_revenue_tokens(stake_address, Some(vec![revenue_address]), &mut self.env)
+    }
+
+    // Mints an NFT collection of 3 NFTs with rarities 1, 2 and 3.
+    fn mint_nfts(&mut self) -> Result<Bucket, RuntimeError> {
+        Ok(ResourceBuilder::new_integer_non_fungible::<Collectible>(OwnerRole::None)
+            .mint_initial_supply(
+                (1..=3u64).map(|nft| (IntegerNonFungibleLocalId::new(nft), Collectible { rarity: Decimal::from(nft) })),
+                &mut self.env,
+            )?
+            .into())
+    }
+
+    // Adds an NFT collection of 3 NFTs with rarities 1, 2 and 3 as stakable, returning the NFTs.
+    fn add_nfts(&mut self, nft_weight: NftWeight) -> Result<Bucket, RuntimeError> {
+        let nfts = self.mint_nfts()?;
+        let nft_address = nfts.resource_address(&mut self.env)?;
+        self.add_stakable(nft_address, None)?;
+        self.staking.set_nft_weight(nft_address, nft_weight, &mut self.env)?;
+        Ok(nfts)
+    }
+
+    // Stakes NFTs of the collection to a new staking ID, returning the staking ID.
+    fn stake_nfts(&mut self, nfts: &mut Bucket, ids: &[u64]) -> Result<Bucket, RuntimeError> {
+        let ids: IndexSet<NonFungibleLocalId> = ids.iter().map(|id| NonFungibleLocalId::integer(*id)).collect();
+        let stake = nfts.take_non_fungibles(ids, &mut self.env)?;
+        self.stake_bucket(stake)
+    }
+
+    // Adds a basket requiring one of both stakable tokens per basket unit, distributing 100 reward tokens every period.
+    fn add_basket(&mut self) -> Result<ResourceAddress, RuntimeError> {
+        let stake_address = self.stake_address();
+        let other_address = self.other_address();
+        self.staking.add_basket(
+            "Pair".to_string(),
+            HashMap::from([(stake_address, dec!(1)), (other_address, dec!(1))]),
+            dec!(100),
+            Lock {
+                payment: dec!(0),
+                duration: 0,
+            },
+            no_limits(),
+            &mut self.env,
+        )
+    }
+
+    // Stakes an amount of basket units to a new staking ID, returning the result of stake_basket.
+    fn stake_basket(
+        &mut self,
+        basket: ResourceAddress,
+        units: Decimal,
+        access_proofs: Vec<AccessProof>,
+    ) -> Result<(Option<Bucket>, Vec<Bucket>), RuntimeError> {
+        let legs = vec![
+            self.stake_tokens.take(units, &mut self.env)?,
+            self.other_tokens.take(units, &mut self.env)?,
+        ];
+        self.staking
+            .stake_basket(legs, basket, None, access_proofs, None, None, &mut self.env)
+    }
+}
+
+fn no_limits() -> StakeLimits {
+    StakeLimits {
+        min_stake: dec!(0),
+        max_total_stake: None,
+        max_stake_per_id: None,
+    }
+}
+
+#[test]
+fn test_stake_and_claim() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+
+    setup.advance_periods(1)?;
+
+    assert_eq!(setup.claim(&id)?, dec!(100));
+    Ok(())
+}
+
+#[test]
+fn test_rewards_split_pro_rata() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id_1 = setup.stake_new(dec!(300))?;
+    let id_2 = setup.stake_new(dec!(100))?;
+
+    setup.advance_periods(1)?;
+
+    assert_eq!(setup.claim(&id_1)?, dec!(75));
+    assert_eq!(setup.claim(&id_2)?, dec!(25));
+    Ok(())
+}
+
+#[test]
+fn test_claim_before_period_end_fails() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+
+    assert!(setup.claim(&id).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_stake_limits_enforced_and_raised() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    setup.set_limits(
+        stake_address,
+        StakeLimits {
+            min_stake: dec!(10),
+            max_total_stake: Some(dec!(150)),
+            max_stake_per_id: Some(dec!(100)),
+        },
+    )?;
+
+    assert!(setup.stake_new(dec!(5)).is_err());
+    let id = setup.stake_new(dec!(100))?;
+    assert!(setup.stake(&id, dec!(10)).is_err());
+    assert!(setup.stake_new(dec!(60)).is_err());
+    setup.stake_new(dec!(50))?;
+
+    // the owner raises both caps
+    setup.set_limits(
+        stake_address,
+        StakeLimits {
+            min_stake: dec!(10),
+            max_total_stake: Some(dec!(300)),
+            max_stake_per_id: Some(dec!(200)),
+        },
+    )?;
+    setup.stake(&id, dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+    assert_eq!(
+        setup.staking.get_stake_at(id_local, stake_address, 0, &mut setup.env)?,
+        dec!(200)
     );
-    println!("{:?}\n", receipt);
-    let component = receipt.expect_commit(true).new_component_addresses()[0];
+    assert!(setup.stake(&id, dec!(10)).is_err());
+    Ok(())
+}
 
-    // Test the `free_token` method.
-    let manifest = ManifestBuilder::new()
-        .call_method(component, "free_token", manifest_args!())
-        .call_method(
+#[test]
+fn test_stake_access_by_badge() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let badge: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .mint_initial_supply(dec!(1), &mut setup.env)?
+        .into();
+    let badge_address = badge.resource_address(&mut setup.env)?;
+    setup
+        .staking
+        .set_stake_access(stake_address, StakeAccess::Badge(badge_address), &mut setup.env)?;
+
+    assert!(setup.stake_new(dec!(100)).is_err());
+    let wrong_proof = setup.controller.create_proof_of_all(&mut setup.env)?;
+    let stake = setup.stake_tokens.take(dec!(100), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .stake(stake, None, vec![AccessProof::Badge(wrong_proof)], None, None, &mut setup.env)
+        .is_err());
+
+    let badge_proof = badge.create_proof_of_all(&mut setup.env)?;
+    let stake = setup.stake_tokens.take(dec!(100), &mut setup.env)?;
+    let id = setup
+        .staking
+        .stake(stake, None, vec![AccessProof::Badge(badge_proof)], None, None, &mut setup.env)?;
+    assert!(id.is_some());
+
+    // the owner opens the stakable up again
+    setup
+        .staking
+        .set_stake_access(stake_address, StakeAccess::Public, &mut setup.env)?;
+    setup.stake_new(dec!(100))?;
+    Ok(())
+}
+
+#[test]
+fn test_finish_unstake_before_delay_fails() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+
+    let receipt = setup.unstake(&id, dec!(100))?;
+    let result = setup.staking.finish_unstake(receipt, &mut setup.env);
+
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_finish_unstake_after_delay() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+
+    let receipt = setup.unstake(&id, dec!(40))?;
+    setup.advance_days(7)?;
+    let unstaked = setup.staking.finish_unstake(receipt, &mut setup.env)?;
+
+    assert_eq!(unstaked.amount(&mut setup.env)?, dec!(40));
+    Ok(())
+}
+
+#[test]
+fn test_pause_blocks_staking() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup.staking.set_pause(
+        Pause {
+            stake: true,
+            unstake: false,
+            claim: false,
+            lock: false,
+            vote: false,
+        },
+        &mut setup.env,
+    )?;
+
+    assert!(setup.stake_new(dec!(100)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_emergency_withdraw_ignores_unstake_delay() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+
+    let proof = setup.id_proof(&id)?;
+    assert!(setup.staking.emergency_withdraw(proof, vec![], &mut setup.env).is_err());
+
+    setup.staking.enable_emergency_exit(&mut setup.env)?;
+    let proof = setup.id_proof(&id)?;
+    let withdrawn = setup.staking.emergency_withdraw(proof, vec![], &mut setup.env)?;
+
+    assert_eq!(withdrawn.len(), 1);
+    assert_eq!(withdrawn[0].amount(&mut setup.env)?, dec!(100));
+    Ok(())
+}
+
+#[test]
+fn test_transfer_receipt_redeemed_in_emergency_exit() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let id = setup.stake_new(dec!(100))?;
+
+    let proof = setup.id_proof(&id)?;
+    let early_receipt = setup
+        .staking
+        .start_unstake(proof, stake_address, dec!(10), true, None, None, &mut setup.env)?;
+    assert!(setup.staking.finish_unstake(early_receipt, &mut setup.env).is_err());
+
+    let proof = setup.id_proof(&id)?;
+    let receipt = setup
+        .staking
+        .start_unstake(proof, stake_address, dec!(40), true, None, None, &mut setup.env)?;
+
+    // stake can no longer be added in emergency exit mode, so the receipt is redeemed for the transferred tokens instead
+    setup.staking.enable_emergency_exit(&mut setup.env)?;
+    let unstaked = setup.staking.finish_unstake(receipt, &mut setup.env)?;
+    assert_eq!(unstaked.resource_address(&mut setup.env)?, stake_address);
+    assert_eq!(unstaked.amount(&mut setup.env)?, dec!(40));
+    Ok(())
+}
+
+fn local_id(env: &mut TestEnvironment, id: &Bucket) -> Result<NonFungibleLocalId, RuntimeError> {
+    Ok(id.non_fungible_local_ids(env)?.into_iter().next().unwrap())
+}
+
+#[test]
+fn test_dao_lock_blocks_unstake_until_expiry() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+    let stake_address = setup.stake_address();
+
+    let lock_until = setup.env.get_current_time().add_days(10).unwrap();
+    setup
+        .staking
+        .set_lock(stake_address, lock_until, id_local, 1, &mut setup.env)?;
+
+    assert!(setup.unstake(&id, dec!(100)).is_err());
+    setup.advance_days(10)?;
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(100)).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_dao_lock_capped_at_max_duration() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+    let stake_address = setup.stake_address();
+
+    let lock_until = setup.env.get_current_time().add_days(31).unwrap();
+    setup
+        .staking
+        .set_lock(stake_address, lock_until, id_local, 1, &mut setup.env)?;
+
+    setup.advance_days(29)?;
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(100)).is_err());
+    setup.advance_days(1)?;
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(100)).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_dao_lock_window_not_extended_by_fresh_proposals() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+    let stake_address = setup.stake_address();
+
+    let lock_until = setup.env.get_current_time().add_days(20).unwrap();
+    setup
+        .staking
+        .set_lock(stake_address, lock_until, id_local.clone(), 1, &mut setup.env)?;
+
+    // the lock of the fresh proposal is capped at the end of the window at day 30
+    setup.advance_days(15)?;
+    let lock_until = setup.env.get_current_time().add_days(20).unwrap();
+    setup
+        .staking
+        .set_lock(stake_address, lock_until, id_local.clone(), 2, &mut setup.env)?;
+
+    // after the window ends, the stake is unlocked for at least a day
+    setup.advance_days(15)?;
+    let lock_until = setup.env.get_current_time().add_days(5).unwrap();
+    let relocked = setup
+        .staking
+        .set_lock(stake_address, lock_until, id_local, 3, &mut setup.env);
+    assert!(relocked.is_err());
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(100)).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_governance_requires_positive_proposal_threshold() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+
+    assert!(setup.governance(id_address, dec!(0)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_create_proposal_below_threshold_fails() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(10))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+    let mut governance = setup.governance(id_address, dec!(50))?;
+    setup.advance_periods(1)?;
+
+    let proof = setup.id_proof(&id)?;
+    let result = governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetMaxClaimDelay(10),
+        &mut setup.env,
+    );
+
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_proposal_accepted_with_snapshot_weight() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(200))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+    let mut governance = setup.governance(id_address, dec!(50))?;
+    setup.advance_periods(1)?;
+
+    let proof = setup.id_proof(&id)?;
+    let proposal_id = governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetMaxClaimDelay(10),
+        &mut setup.env,
+    )?;
+
+    // stake added after the snapshot does not count
+    let late_id = setup.stake_new(dec!(1_000))?;
+    let proof = setup.id_proof(&late_id)?;
+    assert!(governance.vote(proposal_id, proof, false, &mut setup.env).is_err());
+
+    let proof = setup.id_proof(&id)?;
+    governance.vote(proposal_id, proof, true, &mut setup.env)?;
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(200)).is_err());
+
+    setup.advance_days(7)?;
+    let status = governance.finish_proposal(proposal_id, &mut setup.env)?;
+
+    assert!(status == ProposalStatus::Accepted);
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(200)).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_rejected_proposal_releases_locks_without_executing() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(50))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+    let mut governance = setup.governance(id_address, dec!(50))?;
+    setup.advance_periods(1)?;
+
+    let proof = setup.id_proof(&id)?;
+    let proposal_id = governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetUnstakeDelay(0),
+        &mut setup.env,
+    )?;
+    let proof = setup.id_proof(&id)?;
+    governance.vote(proposal_id, proof, true, &mut setup.env)?;
+
+    // the vote weight of 50 does not reach the quorum of 100
+    setup.advance_days(7)?;
+    let status = governance.finish_proposal(proposal_id, &mut setup.env)?;
+    assert!(status == ProposalStatus::Rejected);
+
+    // the stake is unlocked, and the unstaking delay is unchanged
+    setup.claim(&id)?;
+    let receipt = setup.unstake(&id, dec!(50))?;
+    assert!(setup.staking.finish_unstake(receipt, &mut setup.env).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_create_proposal_rejects_failing_actions() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+    let reward_address = setup.reward_tokens.resource_address(&mut setup.env)?;
+    let mut governance = setup.governance(id_address, dec!(50))?;
+    setup.advance_periods(1)?;
+
+    for action in [
+        ProposalAction::SetPeriodInterval(0),
+        ProposalAction::SetMaxClaimDelay(-1),
+        ProposalAction::SetUnstakeDelay(31),
+        ProposalAction::SetRewards(reward_address, dec!(100)),
+    ] {
+        let proof = setup.id_proof(&id)?;
+        assert!(governance
+            .create_proposal(proof, "Test".to_string(), "Test proposal".to_string(), action, &mut setup.env)
+            .is_err());
+    }
+
+    let proof = setup.id_proof(&id)?;
+    governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetUnstakeDelay(30),
+        &mut setup.env,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_vote_lock_capped_at_lock_window() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 10)?;
+    let id = setup.stake_new(dec!(200))?;
+    let id_address = id.resource_address(&mut setup.env)?;
+    let mut governance = setup.governance(id_address, dec!(50))?;
+    setup.advance_periods(1)?;
+
+    let mut proposal_ids = Vec::new();
+    for days in [5, 0] {
+        let proof = setup.id_proof(&id)?;
+        let proposal_id = governance.create_proposal(
+            proof,
+            "Test".to_string(),
+            "Test proposal".to_string(),
+            ProposalAction::SetMaxClaimDelay(10),
+            &mut setup.env,
+        )?;
+        let proof = setup.id_proof(&id)?;
+        governance.vote(proposal_id, proof, true, &mut setup.env)?;
+        proposal_ids.push(proposal_id);
+        setup.advance_days(days)?;
+    }
+
+    // the vote on the second proposal ends at day 19, but the lock window started at day 7 and ends at day 17
+    setup.advance_days(2)?;
+    let status = governance.finish_proposal(proposal_ids[0], &mut setup.env)?;
+    assert!(status == ProposalStatus::Accepted);
+    setup.claim(&id)?;
+    assert!(setup.unstake(&id, dec!(1)).is_err());
+
+    setup.advance_days(3)?;
+    setup.claim(&id)?;
+    setup.unstake(&id, dec!(1))?;
+
+    // votes fail in the unlocked day after the lock window
+    let proof = setup.id_proof(&id)?;
+    let proposal_id = governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetMaxClaimDelay(10),
+        &mut setup.env,
+    )?;
+    let proof = setup.id_proof(&id)?;
+    assert!(governance.vote(proposal_id, proof, true, &mut setup.env).is_err());
+
+    setup.advance_days(1)?;
+    let proof = setup.id_proof(&id)?;
+    governance.vote(proposal_id, proof, true, &mut setup.env)?;
+    Ok(())
+}
+
+#[test]
+fn test_delegated_stake_votes_at_snapshot_and_is_locked() -> Result<(), RuntimeError> {
+    let mut setup = Setup::with_options(true, 30)?;
+    let delegator = setup.stake_new(dec!(100))?;
+    let delegate = setup.stake_new(dec!(100))?;
+    let id_address = delegate.resource_address(&mut setup.env)?;
+    let delegate_local = local_id(&mut setup.env, &delegate)?;
+    let stake_address = setup.stake_address();
+    let mut governance = setup.governance(id_address, dec!(50))?;
+
+    let proof = setup.id_proof(&delegator)?;
+    setup
+        .staking
+        .delegate(proof, stake_address, Delegate::Id(delegate_local.clone()), &mut setup.env)?;
+    setup.advance_periods(1)?;
+    setup.claim(&delegator)?;
+
+    let proof = setup.id_proof(&delegate)?;
+    let proposal_id = governance.create_proposal(
+        proof,
+        "Test".to_string(),
+        "Test proposal".to_string(),
+        ProposalAction::SetMaxClaimDelay(10),
+        &mut setup.env,
+    )?;
+
+    // revoking after the snapshot does not give the delegator its voting power back
+    let proof = setup.id_proof(&delegator)?;
+    setup
+        .staking
+        .revoke_delegation(proof, stake_address, &mut setup.env)?;
+    let proof = setup.id_proof(&delegator)?;
+    assert!(governance.vote(proposal_id, proof, false, &mut setup.env).is_err());
+
+    let proof = setup.id_proof(&delegator)?;
+    setup
+        .staking
+        .delegate(proof, stake_address, Delegate::Id(delegate_local), &mut setup.env)?;
+    let proof = setup.id_proof(&delegate)?;
+    governance.vote(proposal_id, proof, true, &mut setup.env)?;
+
+    // the delegated stake is locked along with the stake of the delegate
+    assert!(setup.unstake(&delegator, dec!(100)).is_err());
+    let proof = setup.id_proof(&delegator)?;
+    assert!(setup
+        .staking
+        .revoke_delegation(proof, stake_address, &mut setup.env)
+        .is_err());
+
+    setup.advance_days(7)?;
+    let status = governance.finish_proposal(proposal_id, &mut setup.env)?;
+    assert!(status == ProposalStatus::Accepted);
+    Ok(())
+}
+
+#[test]
+fn test_stake_checkpoints_per_period() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+    let stake_address = setup.stake_address();
+
+    setup.advance_periods(2)?;
+    setup.claim(&id)?;
+    setup.stake(&id, dec!(50))?;
+    setup.advance_periods(2)?;
+    setup.claim(&id)?;
+    setup.unstake(&id, dec!(120))?;
+
+    let mut stake_at = Vec::new();
+    for period in 0..5 {
+        stake_at.push(
+            setup
+                .staking
+                .get_stake_at(id_local.clone(), stake_address, period, &mut setup.env)?,
+        );
+    }
+    assert_eq!(stake_at, vec![dec!(100), dec!(100), dec!(150), dec!(150), dec!(30)]);
+
+    let total_stake = setup
+        .staking
+        .get_total_stake_at(stake_address, 1, &mut setup.env)?;
+    assert_eq!(total_stake, dec!(100));
+    Ok(())
+}
+
+#[test]
+fn test_warmup_stake_earns_from_activation() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    setup.staking.set_warmup(stake_address, 2, &mut setup.env)?;
+    let early = setup.stake_new(dec!(100))?;
+    setup.advance_periods(2)?;
+    setup.claim(&early)?;
+
+    // stake right before the period ends does not earn the reward of that period
+    let sniper = setup.stake_new(dec!(100))?;
+    setup.advance_periods(1)?;
+
+    assert_eq!(setup.claim(&early)?, dec!(100));
+    assert_eq!(setup.claim(&sniper)?, dec!(0));
+
+    setup.advance_periods(2)?;
+    assert_eq!(setup.claim(&early)?, dec!(150));
+    assert_eq!(setup.claim(&sniper)?, dec!(50));
+    Ok(())
+}
+
+#[test]
+fn test_unstake_with_unclaimed_warmup_periods_fails() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    setup.staking.set_warmup(stake_address, 2, &mut setup.env)?;
+    let other = setup.stake_new(dec!(100))?;
+    let id = setup.stake_new(dec!(100))?;
+
+    // the stake of the staking ID activates in period 2, so it earns nothing in periods 0 and 1
+    setup.advance_periods(3)?;
+    assert!(setup.unstake(&id, dec!(1)).is_err());
+
+    assert_eq!(setup.claim(&id)?, dec!(50));
+    setup.unstake(&id, dec!(1))?;
+    assert_eq!(setup.claim(&other)?, dec!(50));
+    Ok(())
+}
+
+#[test]
+fn test_referrer_needs_stake_from_previous_period() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup
+        .staking
+        .set_referral_percentage(dec!("0.1"), &mut setup.env)?;
+    let referral_budget = setup.reward_tokens.take(dec!(1_000), &mut setup.env)?;
+    setup
+        .staking
+        .fill_referral_rewards(referral_budget, &mut setup.env)?;
+
+    let referrer = setup.stake_new(dec!(100))?;
+    let referrer_local = local_id(&mut setup.env, &referrer)?;
+
+    // a fresh staking ID cannot refer yet
+    let result = setup
+        .staking
+        .create_id(Some(referrer_local.clone()), &mut setup.env);
+    assert!(result.is_err());
+
+    setup.advance_periods(1)?;
+    setup.claim(&referrer)?;
+
+    let stake = setup.stake_tokens.take(dec!(100), &mut setup.env)?;
+    let referee = setup
+        .staking
+        .stake(stake, None, vec![], Some(referrer_local), None, &mut setup.env)?
+        .unwrap();
+    setup.advance_periods(1)?;
+
+    assert_eq!(setup.claim(&referee)?, dec!(50));
+    assert_eq!(setup.claim(&referrer)?, dec!(55));
+    Ok(())
+}
+
+#[test]
+fn test_claim_for_calls_payout_component() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let id = setup.stake_new(dec!(100))?;
+    let id_local = local_id(&mut setup.env, &id)?;
+
+    // a second staking component, of which the reward vault receives the payout through fill_rewards
+    let controller_address = setup.controller.resource_address(&mut setup.env)?;
+    let empty_rewards = setup.reward_tokens.take(dec!(0), &mut setup.env)?;
+    let mut treasury = Staking::new(
+        controller_address,
+        FungibleBucket(empty_rewards),
+        7,
+        "Treasury".to_string(),
+        "TRS".to_string(),
+        false,
+        30,
+        30,
+        false,
+        controller_address,
+        setup.package_address,
+        &mut setup.env,
+    )?;
+    let treasury_address = ComponentAddress::try_from(treasury.0.as_bytes()).unwrap();
+
+    assert!(setup.staking.claim_for(id_local.clone(), &mut setup.env).is_err());
+    let proof = setup.id_proof(&id)?;
+    setup.staking.set_payout_destination(
+        proof,
+        Some(PayoutDestination::Component(treasury_address, "fill_rewards".to_string())),
+        &mut setup.env,
+    )?;
+
+    setup.advance_periods(1)?;
+    setup.staking.claim_for(id_local, &mut setup.env)?;
+    let payout = treasury.remove_rewards(dec!(100), &mut setup.env)?;
+    assert_eq!(payout.amount(&mut setup.env)?, dec!(100));
+    assert_eq!(setup.claim(&id)?, dec!(0));
+    Ok(())
+}
+
+#[test]
+fn test_order_book_sells_part_of_stake_transfer_receipt() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let seller = setup.stake_new(dec!(100))?;
+    let buyer = setup.stake_new(dec!(1))?;
+    let buyer_local = local_id(&mut setup.env, &buyer)?;
+    let price_address = setup.reward_tokens.resource_address(&mut setup.env)?;
+
+    let mut order_book = OrderBook::new(setup.staking, "Test".to_string(), setup.package_address, &mut setup.env)?;
+
+    let proof = setup.id_proof(&seller)?;
+    let receipt = setup
+        .staking
+        .start_unstake(proof, stake_address, dec!(40), true, None, None, &mut setup.env)?;
+    let order_badge = order_book.list(NonFungibleBucket(receipt), price_address, dec!(2), &mut setup.env)?;
+
+    let payment = setup.reward_tokens.take(dec!(25), &mut setup.env)?;
+    let (bought_receipt, change) = order_book.buy(1, dec!(10), payment, &mut setup.env)?;
+    assert_eq!(change.amount(&mut setup.env)?, dec!(5));
+    assert_eq!(
+        order_book.get_order(1, &mut setup.env)?,
+        (stake_address, dec!(30), price_address, dec!(2))
+    );
+
+    let proof = setup.id_proof(&buyer)?;
+    setup
+        .staking
+        .stake(bought_receipt, Some(proof), vec![], None, None, &mut setup.env)?;
+    let period = setup.staking.get_current_period(&mut setup.env)?;
+    assert_eq!(
+        setup.staking.get_stake_at(buyer_local, stake_address, period, &mut setup.env)?,
+        dec!(11)
+    );
+
+    let (remaining_receipt, proceeds) = order_book.cancel(NonFungibleBucket(order_badge), &mut setup.env)?;
+    assert_eq!(proceeds.amount(&mut setup.env)?, dec!(20));
+    assert!(remaining_receipt.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_order_book_rejects_foreign_receipts() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let price_address = setup.reward_tokens.resource_address(&mut setup.env)?;
+    let mut order_book = OrderBook::new(setup.staking, "Test".to_string(), setup.package_address, &mut setup.env)?;
+
+    let other_id = setup.stake_new(dec!(100))?;
+    let result = order_book.list(NonFungibleBucket(other_id), price_address, dec!(1), &mut setup.env);
+
+    assert!(result.is_err());
+    Ok(())
+}
+
+// Sets up gauge voting with the stakable token as vote resource, and whitelists the reward token as bribe token.
+fn setup_bribes(setup: &mut Setup) -> Result<(), RuntimeError> {
+    let stake_address = setup.stake_address();
+    let bribe_address = setup.reward_tokens.resource_address(&mut setup.env)?;
+    setup.set_gauge(HashMap::from([(stake_address, dec!(1))]), Some(stake_address), false)?;
+    setup
+        .staking
+        .set_bribe_token(bribe_address, Some(dec!(10)), &mut setup.env)
+}
+
+// Claims the rewards of a staking ID, returning the bribes claimed with them.
+fn claim_bribes(setup: &mut Setup, id: &Bucket) -> Result<Vec<Bucket>, RuntimeError> {
+    let proof = setup.id_proof(id)?;
+    let (_, bribes) = setup.staking.update_id(proof, &mut setup.env)?;
+    Ok(bribes)
+}
+
+#[test]
+fn test_bribes_claimed_pro_rata_and_once() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup_bribes(&mut setup)?;
+    let stake_address = setup.stake_address();
+    let id_1 = setup.stake_new(dec!(300))?;
+    let id_2 = setup.stake_new(dec!(100))?;
+    setup.advance_periods(1)?;
+
+    for id in [&id_1, &id_2] {
+        let proof = setup.id_proof(id)?;
+        setup
+            .staking
+            .vote_gauges(proof, HashMap::from([(stake_address, dec!(1))]), &mut setup.env)?;
+    }
+    let bribe = setup.reward_tokens.take(dec!(100), &mut setup.env)?;
+    setup.staking.deposit_bribe(bribe, stake_address, 1, None, &mut setup.env)?;
+
+    // bribes of a period can only be claimed once it has ended
+    assert!(claim_bribes(&mut setup, &id_1)?.is_empty());
+
+    setup.advance_periods(1)?;
+    let bribes = claim_bribes(&mut setup, &id_1)?;
+    assert_eq!(bribes.len(), 1);
+    assert_eq!(bribes[0].amount(&mut setup.env)?, dec!(75));
+    assert!(claim_bribes(&mut setup, &id_1)?.is_empty());
+
+    let bribes = claim_bribes(&mut setup, &id_2)?;
+    assert_eq!(bribes[0].amount(&mut setup.env)?, dec!(25));
+    Ok(())
+}
+
+#[test]
+fn test_bribes_need_whitelisted_token_and_are_capped() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup_bribes(&mut setup)?;
+    let stake_address = setup.stake_address();
+    setup.staking.set_max_bribes(2, &mut setup.env)?;
+
+    let unlisted = setup.stake_tokens.take(dec!(100), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .deposit_bribe(unlisted, stake_address, 0, None, &mut setup.env)
+        .is_err());
+    let too_small = setup.reward_tokens.take(dec!(5), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .deposit_bribe(too_small, stake_address, 0, None, &mut setup.env)
+        .is_err());
+
+    let bribe = setup.reward_tokens.take(dec!(10), &mut setup.env)?;
+    setup
+        .staking
+        .deposit_bribe(bribe, stake_address, 0, Some(FAUCET), &mut setup.env)?;
+    let bribe = setup.reward_tokens.take(dec!(10), &mut setup.env)?;
+    let merged_id = setup.staking.deposit_bribe(bribe, stake_address, 0, None, &mut setup.env)?;
+
+    let bribe = setup.reward_tokens.take(dec!(10), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .deposit_bribe(bribe, stake_address, 0, Some(FAUCET), &mut setup.env)
+        .is_err());
+
+    // bribes without refund account are merged, so they do not take up another slot
+    let bribe = setup.reward_tokens.take(dec!(10), &mut setup.env)?;
+    let bribe_id = setup.staking.deposit_bribe(bribe, stake_address, 0, None, &mut setup.env)?;
+    assert_eq!(bribe_id, merged_id);
+    Ok(())
+}
+
+#[test]
+fn test_revenue_replaces_rewards() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup.accept_revenue()?;
+    let stake_address = setup.stake_address();
+    let id = setup.stake_new(dec!(100))?;
+
+    let revenue = setup.reward_tokens.take(dec!(50), &mut setup.env)?;
+    setup.staking.deposit_revenue(revenue, stake_address, &mut setup.env)?;
+    setup.advance_periods(1)?;
+
+    let proof = setup.id_proof(&id)?;
+    let (rewards, revenue) = setup.staking.update_id(proof, &mut setup.env)?;
+    assert_eq!(rewards.amount(&mut setup.env)?, dec!(0));
+    assert_eq!(revenue.len(), 1);
+    assert_eq!(revenue[0].amount(&mut setup.env)?, dec!(50));
+    Ok(())
+}
+
+#[test]
+fn test_revenue_only_accepted_in_revenue_tokens() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+
+    // a regular stakable does not accept revenue
+    let revenue = setup.reward_tokens.take(dec!(50), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .deposit_revenue(revenue, stake_address, &mut setup.env)
+        .is_err());
+
+    setup.accept_revenue()?;
+    let revenue = setup.stake_tokens.take(dec!(50), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .deposit_revenue(revenue, stake_address, &mut setup.env)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sweep_unclaimed_revenue_after_claim_delay() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup.accept_revenue()?;
+    let stake_address = setup.stake_address();
+    setup.stake_new(dec!(100))?;
+
+    let revenue = setup.reward_tokens.take(dec!(50), &mut setup.env)?;
+    setup.staking.deposit_revenue(revenue, stake_address, &mut setup.env)?;
+    setup.advance_periods(2)?;
+    assert!(setup.staking.sweep_revenue(stake_address, 0, &mut setup.env).is_err());
+
+    // the default max claim delay is 5 periods
+    setup.advance_periods(4)?;
+    let swept = setup.staking.sweep_revenue(stake_address, 0, &mut setup.env)?;
+    assert_eq!(swept[0].amount(&mut setup.env)?, dec!(50));
+    Ok(())
+}
+
+#[test]
+fn test_stream_drips_evenly_with_remainder_in_last_period() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let id = setup.stake_new(dec!(1))?;
+
+    let funding = setup.reward_tokens.take(dec!(100), &mut setup.env)?;
+    setup.staking.fund_stream(funding, stake_address, 3, &mut setup.env)?;
+    assert_eq!(
+        setup.staking.get_stream(stake_address, &mut setup.env)?,
+        vec![
+            (0, dec!("33.333333333333333333")),
+            (1, dec!("33.333333333333333333")),
+            (2, dec!("33.333333333333333334")),
+        ]
+    );
+
+    // the stream is distributed on top of the reward amount of 100, and removed from the schedule once distributed
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!("133.333333333333333333"));
+    assert_eq!(setup.staking.get_stream(stake_address, &mut setup.env)?.len(), 2);
+    setup.advance_periods(2)?;
+    assert_eq!(setup.claim(&id)?, dec!("266.666666666666666667"));
+    assert!(setup.staking.get_stream(stake_address, &mut setup.env)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_stream_fundings_stack() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let id = setup.stake_new(dec!(1))?;
+
+    let funding = setup.reward_tokens.take(dec!(20), &mut setup.env)?;
+    setup.staking.fund_stream(funding, stake_address, 2, &mut setup.env)?;
+    setup.advance_periods(1)?;
+    let funding = setup.reward_tokens.take(dec!(30), &mut setup.env)?;
+    setup.staking.fund_stream(funding, stake_address, 3, &mut setup.env)?;
+    assert_eq!(
+        setup.staking.get_stream(stake_address, &mut setup.env)?,
+        vec![(1, dec!(20)), (2, dec!(10)), (3, dec!(10))]
+    );
+
+    setup.advance_periods(2)?;
+    assert_eq!(setup.claim(&id)?, dec!(100) + dec!(10) + dec!(100) + dec!(20) + dec!(100) + dec!(10));
+    Ok(())
+}
+
+#[test]
+fn test_stream_rejected_for_revenue_stakables() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    setup.accept_revenue()?;
+    let stake_address = setup.stake_address();
+
+    let funding = setup.reward_tokens.take(dec!(100), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .fund_stream(funding, stake_address, 3, &mut setup.env)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_target_apr_rejects_negative_values() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let target_apr = TargetApr {
+        apr: dec!("0.1"),
+        price_ratio: dec!(1),
+        max_per_period: dec!(100),
+        budget: dec!(1_000),
+    };
+
+    for negative in [
+        TargetApr { apr: dec!(-1), ..target_apr.clone() },
+        TargetApr { price_ratio: dec!(-1), ..target_apr.clone() },
+        TargetApr { max_per_period: dec!(-1), ..target_apr.clone() },
+        TargetApr { budget: dec!(-1), ..target_apr.clone() },
+    ] {
+        assert!(setup
+            .staking
+            .set_target_apr(stake_address, Some(negative), &mut setup.env)
+            .is_err());
+    }
+
+    setup
+        .staking
+        .set_target_apr(stake_address, Some(target_apr), &mut setup.env)?;
+    assert!(setup
+        .staking
+        .set_price_ratio(stake_address, dec!(-1), &mut setup.env)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_gauge_emission_split_by_owner_weights() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let other_address = setup.other_address();
+    setup.set_gauge(
+        HashMap::from([(stake_address, dec!(3)), (other_address, dec!(1))]),
+        None,
+        false,
+    )?;
+    let id = setup.stake_new(dec!(100))?;
+    let other_id = setup.stake_other_new(dec!(100))?;
+
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(75));
+    assert_eq!(setup.claim(&other_id)?, dec!(25));
+    Ok(())
+}
+
+#[test]
+fn test_gauge_emission_split_by_votes() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let other_address = setup.other_address();
+    setup.set_gauge(
+        HashMap::from([(stake_address, dec!(1)), (other_address, dec!(1))]),
+        Some(stake_address),
+        false,
+    )?;
+    let id = setup.stake_new(dec!(100))?;
+    let other_id = setup.stake_other_new(dec!(100))?;
+
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(50));
+    assert_eq!(setup.claim(&other_id)?, dec!(50));
+
+    // the votes of the period replace the owner weights
+    let proof = setup.id_proof(&id)?;
+    setup
+        .staking
+        .vote_gauges(proof, HashMap::from([(other_address, dec!(1))]), &mut setup.env)?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(0));
+    assert_eq!(setup.claim(&other_id)?, dec!(100));
+    Ok(())
+}
+
+#[test]
+fn test_gauge_split_excludes_target_apr_stakables() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let other_address = setup.other_address();
+    setup.set_gauge(
+        HashMap::from([(stake_address, dec!(1)), (other_address, dec!(1))]),
+        None,
+        false,
+    )?;
+    setup.staking.set_target_apr(
+        other_address,
+        Some(TargetApr {
+            apr: dec!("3.65"),
+            price_ratio: dec!(1),
+            max_per_period: dec!(100),
+            budget: dec!(1_000),
+        }),
+        &mut setup.env,
+    )?;
+    let id = setup.stake_new(dec!(100))?;
+    let other_id = setup.stake_other_new(dec!(100))?;
+
+    // the full emission goes to the stakable without target APR, the other earns 100 * 3.65 * 7 / 365
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(100));
+    assert_eq!(setup.claim(&other_id)?, dec!(7));
+    Ok(())
+}
+
+#[test]
+fn test_gauge_voting_paused_and_disabled_in_emergency() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    setup.set_gauge(HashMap::from([(stake_address, dec!(1))]), Some(stake_address), false)?;
+    let id = setup.stake_new(dec!(100))?;
+    setup.advance_periods(1)?;
+
+    setup.staking.set_pause(
+        Pause {
+            vote: true,
+            ..Pause::default()
+        },
+        &mut setup.env,
+    )?;
+    let proof = setup.id_proof(&id)?;
+    assert!(setup
+        .staking
+        .vote_gauges(proof, HashMap::from([(stake_address, dec!(1))]), &mut setup.env)
+        .is_err());
+
+    setup.staking.set_pause(Pause::default(), &mut setup.env)?;
+    setup.staking.enable_emergency_exit(&mut setup.env)?;
+    let proof = setup.id_proof(&id)?;
+    assert!(setup
+        .staking
+        .vote_gauges(proof, HashMap::from([(stake_address, dec!(1))]), &mut setup.env)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_target_apr_based_on_active_stake() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    setup.staking.set_target_apr(
+        stake_address,
+        Some(TargetApr {
+            apr: dec!("3.65"),
+            price_ratio: dec!(1),
+            max_per_period: dec!(100),
+            budget: dec!(1_000),
+        }),
+        &mut setup.env,
+    )?;
+    let id = setup.stake_new(dec!(100))?;
+    setup.staking.set_warmup(stake_address, 1, &mut setup.env)?;
+    let warming_id = setup.stake_new(dec!(900))?;
+
+    // stake that is still warming up earns no target APR, so only 100 * 3.65 * 7 / 365 is emitted
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(7));
+    assert_eq!(setup.claim(&warming_id)?, dec!(0));
+
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(7));
+    assert_eq!(setup.claim(&warming_id)?, dec!(63));
+    Ok(())
+}
+
+#[test]
+fn test_value_weights_fall_back_without_price() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let other_address = setup.other_address();
+    let mut price_feed = setup.price_feed()?;
+    setup.set_gauge(
+        HashMap::from([(stake_address, dec!(1)), (other_address, dec!(1))]),
+        None,
+        true,
+    )?;
+    let id = setup.stake_new(dec!(100))?;
+    let other_id = setup.stake_other_new(dec!(100))?;
+
+    // only one of the stakables is priced, so the gauge weights are used as they are
+    price_feed.set_price(stake_address, dec!(1), &mut setup.env)?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(50));
+    assert_eq!(setup.claim(&other_id)?, dec!(50));
+
+    price_feed.set_price(other_address, dec!(3), &mut setup.env)?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(25));
+    assert_eq!(setup.claim(&other_id)?, dec!(75));
+    Ok(())
+}
+
+#[test]
+fn test_empty_pool_unit_stakable_gets_no_value_weight() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+
+    // a pool of the stakable token, of which the pool units are staked in a second stakable
+    let mut pool = OneResourcePool::instantiate(OwnerRole::None, rule!(allow_all), stake_address, None, &mut setup.env)?;
+    let contribution = setup.stake_tokens.take(dec!(100), &mut setup.env)?;
+    let pool_units = pool.contribute(contribution.into(), &mut setup.env)?;
+    let pool_unit_address = pool_units.resource_address(&mut setup.env)?;
+    let pool_address = ComponentAddress::try_from(pool.0.as_node_id().as_bytes()).unwrap();
+    setup.add_stakable(pool_unit_address, Some(PoolUnit::One(pool_address, stake_address)))?;
+
+    let mut price_feed = setup.price_feed()?;
+    price_feed.set_price(stake_address, dec!(1), &mut setup.env)?;
+    setup.set_gauge(
+        HashMap::from([(stake_address, dec!(1)), (pool_unit_address, dec!(1))]),
+        None,
+        true,
+    )?;
+
+    // nothing is staked in the pool unit stakable, so it is not priced and the full emission goes to the other stakable
+    let id = setup.stake_new(dec!(100))?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&id)?, dec!(100));
+    Ok(())
+}
+
+// NFT data of the NFT collection used as stakable, of which the rarity can be used as weight.
+#[derive(ScryptoSbor, NonFungibleData)]
+struct Collectible {
+    rarity: Decimal,
+}
+
+#[test]
+fn test_nft_stake_weighted_by_table() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let mut nfts = setup.add_nfts(NftWeight::Table(
+        HashMap::from([(NonFungibleLocalId::integer(1), dec!(3))]),
+        dec!(1),
+    ))?;
+    let nft_address = nfts.resource_address(&mut setup.env)?;
+    let rare = setup.stake_nfts(&mut nfts, &[1])?;
+    let common = setup.stake_nfts(&mut nfts, &[2])?;
+    let rare_local = local_id(&mut setup.env, &rare)?;
+
+    assert_eq!(setup.staking.get_stake_at(rare_local, nft_address, 0, &mut setup.env)?, dec!(3));
+    assert_eq!(setup.staking.get_total_stake_at(nft_address, 0, &mut setup.env)?, dec!(4));
+
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&rare)?, dec!(75));
+    assert_eq!(setup.claim(&common)?, dec!(25));
+    Ok(())
+}
+
+#[test]
+fn test_nft_stake_weighted_by_field() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let mut nfts = setup.add_nfts(NftWeight::Field(0))?;
+    let nft_address = nfts.resource_address(&mut setup.env)?;
+    let id = setup.stake_nfts(&mut nfts, &[1, 3])?;
+    let local = local_id(&mut setup.env, &id)?;
+
+    assert_eq!(setup.staking.get_stake_at(local, nft_address, 0, &mut setup.env)?, dec!(4));
+    Ok(())
+}
+
+#[test]
+fn test_unstake_nfts() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let mut nfts = setup.add_nfts(NftWeight::Field(0))?;
+    let nft_address = nfts.resource_address(&mut setup.env)?;
+    let id = setup.stake_nfts(&mut nfts, &[1, 3])?;
+    let local = local_id(&mut setup.env, &id)?;
+
+    // NFT stakables do not support stake transfers, and only staked NFTs can be unstaked
+    let proof = setup.id_proof(&id)?;
+    assert!(setup
+        .staking
+        .start_unstake(proof, nft_address, dec!(0), true, None, Some(vec![NonFungibleLocalId::integer(3)]), &mut setup.env)
+        .is_err());
+    let proof = setup.id_proof(&id)?;
+    assert!(setup
+        .staking
+        .start_unstake(proof, nft_address, dec!(0), false, None, Some(vec![NonFungibleLocalId::integer(2)]), &mut setup.env)
+        .is_err());
+
+    let proof = setup.id_proof(&id)?;
+    let receipt = setup.staking.start_unstake(
+        proof,
+        nft_address,
+        dec!(0),
+        false,
+        None,
+        Some(vec![NonFungibleLocalId::integer(3)]),
+        &mut setup.env,
+    )?;
+    assert_eq!(setup.staking.get_stake_at(local, nft_address, 0, &mut setup.env)?, dec!(1));
+
+    setup.advance_days(7)?;
+    let unstaked = setup.staking.finish_unstake(receipt, &mut setup.env)?;
+    assert_eq!(
+        unstaked.non_fungible_local_ids(&mut setup.env)?,
+        indexset!(NonFungibleLocalId::integer(3))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_basket_counts_towards_leg_limits() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let basket = setup.add_basket()?;
+    let stake_address = setup.stake_address();
+    setup.set_limits(
+        stake_address,
+        StakeLimits {
+            min_stake: dec!(0),
+            max_total_stake: Some(dec!(150)),
+            max_stake_per_id: None,
+        },
+    )?;
+
+    setup.stake_new(dec!(100))?;
+    assert!(setup.stake_basket(basket, dec!(60), vec![]).is_err());
+    setup.stake_basket(basket, dec!(50), vec![])?;
+
+    // the leg tokens held for the basket fill the capacity of the leg
+    assert!(setup.stake_new(dec!(1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_basket_requires_leg_access() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let basket = setup.add_basket()?;
+    let other_address = setup.other_address();
+    let badge: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .mint_initial_supply(dec!(1), &mut setup.env)?
+        .into();
+    let badge_address = badge.resource_address(&mut setup.env)?;
+    setup
+        .staking
+        .set_stake_access(other_address, StakeAccess::Badge(badge_address), &mut setup.env)?;
+
+    assert!(setup.stake_basket(basket, dec!(10), vec![]).is_err());
+
+    let badge_proof = badge.create_proof_of_all(&mut setup.env)?;
+    let (id, _remainder) = setup.stake_basket(basket, dec!(10), vec![AccessProof::Badge(badge_proof)])?;
+    assert!(id.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_basket_legs_with_different_badges() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let basket = setup.add_basket()?;
+    let mut badges: Vec<Bucket> = vec![];
+    for address in [setup.stake_address(), setup.other_address()] {
+        let badge: Bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+            .mint_initial_supply(dec!(1), &mut setup.env)?
+            .into();
+        let badge_address = badge.resource_address(&mut setup.env)?;
+        setup
+            .staking
+            .set_stake_access(address, StakeAccess::Badge(badge_address), &mut setup.env)?;
+        badges.push(badge);
+    }
+
+    // a proof of only one of the badges does not give access to the other leg
+    let badge_proof = badges[0].create_proof_of_all(&mut setup.env)?;
+    assert!(setup
+        .stake_basket(basket, dec!(10), vec![AccessProof::Badge(badge_proof)])
+        .is_err());
+
+    let mut access_proofs: Vec<AccessProof> = vec![];
+    for badge in badges.iter() {
+        access_proofs.push(AccessProof::Badge(badge.create_proof_of_all(&mut setup.env)?));
+    }
+    let (id, _remainder) = setup.stake_basket(basket, dec!(10), access_proofs)?;
+    assert!(id.is_some());
+    Ok(())
+}
+
+// Bonds NFTs of a boost collection to a staking ID.
+fn bond_boost(setup: &mut Setup, id: &Bucket, boost_nfts: &mut Bucket, ids: &[u64]) -> Result<(), RuntimeError> {
+    let ids: IndexSet<NonFungibleLocalId> = ids.iter().map(|id| NonFungibleLocalId::integer(*id)).collect();
+    let bonded = boost_nfts.take_non_fungibles(ids, &mut setup.env)?;
+    let proof = setup.id_proof(id)?;
+    setup
+        .staking
+        .bond_boost(proof, NonFungibleBucket(bonded), &mut setup.env)
+}
+
+#[test]
+fn test_boost_per_nft_multiplies_rewards_until_unbonded() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let mut boost_nfts = setup.mint_nfts()?;
+    let boost_address = boost_nfts.resource_address(&mut setup.env)?;
+    setup.staking.set_boost(
+        boost_address,
+        BoostTable::PerNft(HashMap::from([(NonFungibleLocalId::integer(1), dec!(3))])),
+        &mut setup.env,
+    )?;
+    let boosted = setup.stake_new(dec!(100))?;
+    let plain = setup.stake_new(dec!(100))?;
+
+    // NFT 2 has no multiplier in the table, so only NFT 1 boosts
+    bond_boost(&mut setup, &boosted, &mut boost_nfts, &[1, 2])?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&boosted)?, dec!(75));
+    assert_eq!(setup.claim(&plain)?, dec!(25));
+
+    let proof = setup.id_proof(&boosted)?;
+    let unbonded = setup
+        .staking
+        .unbond_boost(proof, vec![NonFungibleLocalId::integer(1)], &mut setup.env)?;
+    assert_eq!(unbonded.0.amount(&mut setup.env)?, dec!(1));
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&boosted)?, dec!(50));
+    assert_eq!(setup.claim(&plain)?, dec!(50));
+    Ok(())
+}
+
+#[test]
+fn test_boost_by_count_multiplies_rewards_until_unbonded() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let mut boost_nfts = setup.mint_nfts()?;
+    let boost_address = boost_nfts.resource_address(&mut setup.env)?;
+    setup.staking.set_boost(
+        boost_address,
+        BoostTable::Count(vec![(2, dec!(3))]),
+        &mut setup.env,
+    )?;
+    let boosted = setup.stake_new(dec!(100))?;
+    let plain = setup.stake_new(dec!(100))?;
+
+    // a single NFT does not reach the table
+    bond_boost(&mut setup, &boosted, &mut boost_nfts, &[1])?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&boosted)?, dec!(50));
+    assert_eq!(setup.claim(&plain)?, dec!(50));
+
+    bond_boost(&mut setup, &boosted, &mut boost_nfts, &[2])?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&boosted)?, dec!(75));
+    assert_eq!(setup.claim(&plain)?, dec!(25));
+
+    let proof = setup.id_proof(&boosted)?;
+    setup
+        .staking
+        .unbond_boost(proof, vec![NonFungibleLocalId::integer(2)], &mut setup.env)?;
+    setup.advance_periods(1)?;
+    assert_eq!(setup.claim(&boosted)?, dec!(50));
+    assert_eq!(setup.claim(&plain)?, dec!(50));
+
+    // NFTs that are not bonded can not be unbonded
+    let proof = setup.id_proof(&boosted)?;
+    assert!(setup
+        .staking
+        .unbond_boost(proof, vec![NonFungibleLocalId::integer(3)], &mut setup.env)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_loyalty_schedule_validated() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+
+    for schedule in [
+        vec![],
+        vec![(-1, dec!(2))],
+        vec![(1, dec!(-2))],
+        vec![(2, dec!(2)), (1, dec!(3))],
+        vec![(1, dec!(3)), (2, dec!(2))],
+    ] {
+        let loyalty = Loyalty {
+            schedule,
+            cap: dec!(3),
+            decay: false,
+        };
+        assert!(setup
+            .staking
+            .set_loyalty(stake_address, Some(loyalty), &mut setup.env)
+            .is_err());
+    }
+
+    let loyalty = Loyalty {
+        schedule: vec![(1, dec!(2)), (2, dec!(3))],
+        cap: dec!(3),
+        decay: false,
+    };
+    setup
+        .staking
+        .set_loyalty(stake_address, Some(loyalty), &mut setup.env)?;
+    Ok(())
+}
+
+#[test]
+fn test_loyalty_multiplier_applies_every_period() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let loyalty = Loyalty {
+        schedule: vec![(1, dec!(3))],
+        cap: dec!(3),
+        decay: false,
+    };
+    setup
+        .staking
+        .set_loyalty(stake_address, Some(loyalty.clone()), &mut setup.env)?;
+    let loyal = setup.stake_new(dec!(100))?;
+    setup.advance_periods(1)?;
+    let late = setup.stake_new(dec!(100))?;
+
+    // the schedule can not change while its scheduled weight changes are in place
+    assert!(setup
+        .staking
+        .set_loyalty(stake_address, Some(loyalty), &mut setup.env)
+        .is_err());
+
+    // without claiming in between, the loyal staking ID earns 100, then 300 / 400 of the rewards, then half once both reached the multiplier of 3
+    setup.advance_periods(2)?;
+    assert_eq!(setup.claim(&loyal)?, dec!(225));
+    assert_eq!(setup.claim(&late)?, dec!(75));
+    Ok(())
+}
+
+#[test]
+fn test_loyalty_multiplier_reset_on_unstake() -> Result<(), RuntimeError> {
+    let mut setup = Setup::new()?;
+    let stake_address = setup.stake_address();
+    let loyalty = Loyalty {
+        schedule: vec![(1, dec!(3))],
+        cap: dec!(3),
+        decay: false,
+    };
+    setup
+        .staking
+        .set_loyalty(stake_address, Some(loyalty), &mut setup.env)?;
+    let loyal = setup.stake_new(dec!(100))?;
+    let unstaking = setup.stake_new(dec!(200))?;
+    setup.advance_periods(1)?;
+    setup.claim(&loyal)?;
+    setup.claim(&unstaking)?;
+
+    // unstaking restarts the staking duration, so the remaining 100 count as 100 for a period, and as 300 from the next one
+    setup.unstake(&unstaking, dec!(100))?;
+    setup.advance_periods(2)?;
+    assert_eq!(setup.claim(&loyal)?, dec!(75) + dec!(50));
+    assert_eq!(setup.claim(&unstaking)?, dec!(25) + dec!(50));
+    Ok(())
+}
+
+// Staking component instantiated through transactions on a ledger with accounts, with a stakable token that has a stake token, used for tests that need accounts or auth.
+struct RunnerSetup {
+    test_runner: DefaultTestRunner,
+    account: ComponentAddress,
+    public_key: Secp256k1PublicKey,
+    component: ComponentAddress,
+    controller: ResourceAddress,
+    guardian: ResourceAddress,
+    id_address: ResourceAddress,
+    reward_token: ResourceAddress,
+    stake_address: ResourceAddress,
+    stake_token: ResourceAddress,
+    round: u64,
+}
+
+impl RunnerSetup {
+    fn new() -> Self {
+        Self::with_soulbound_ids(false)
+    }
+
+    fn with_soulbound_ids(soulbound_ids: bool) -> Self {
+        let mut test_runner = TestRunnerBuilder::new().build();
+        let (public_key, _private_key, account) = test_runner.new_allocated_account();
+        let package_address = test_runner.compile_and_publish(this_package!());
+
+        let controller = test_runner.create_fungible_resource(dec!(1), 0, account);
+        let guardian = test_runner.create_fungible_resource(dec!(1), 0, account);
+        let reward_token = test_runner.create_fungible_resource(dec!(10_000), 18, account);
+        let stake_address = test_runner.create_fungible_resource(dec!(10_000), 18, account);
+
+        let manifest = ManifestBuilder::new()
+            .withdraw_from_account(account, reward_token, dec!(10_000))
+            .take_all_from_worktop(reward_token, "rewards")
+            .call_function_with_name_lookup(package_address, "Staking", "new", |lookup| {
+                (
+                    controller,
+                    lookup.bucket("rewards"),
+                    7i64,
+                    "Test".to_string(),
+                    "TST".to_string(),
+                    false,
+                    30i64,
+                    30i64,
+                    soulbound_ids,
+                    guardian,
+                )
+            })
+            .build();
+        let receipt = test_runner.execute_manifest_ignoring_fee(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        );
+        let commit = receipt.expect_commit(true);
+        let component = commit.new_component_addresses()[0];
+        let id_address = commit.new_resource_addresses()[0];
+
+        let manifest = ManifestBuilder::new()
+            .create_proof_from_account_of_amount(account, controller, dec!(1))
+            .call_method(
+                component,
+                "add_stakable",
+                manifest_args!(
+                    stake_address,
+                    dec!(100),
+                    (dec!(0), 0i64),
+                    (dec!(0), None::<Decimal>, None::<Decimal>),
+                    true,
+                    None::<()>
+                ),
+            )
+            .build();
+        let receipt = test_runner.execute_manifest_ignoring_fee(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        );
+        let stake_token = receipt.expect_commit(true).new_resource_addresses()[0];
+
+        Self {
+            test_runner,
             account,
-            "deposit_batch",
-            manifest_args!(ManifestExpression::EntireWorktop),
+            public_key,
+            component,
+            controller,
+            guardian,
+            id_address,
+            reward_token,
+            stake_address,
+            stake_token,
+            round: 0,
+        }
+    }
+
+    fn execute(&mut self, manifest: TransactionManifestV1) -> TransactionReceiptV1 {
+        self.test_runner.execute_manifest_ignoring_fee(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&self.public_key)],
         )
+    }
+
+    // Moves the ledger clock forward by a number of days, by starting a new consensus round.
+    fn advance_days(&mut self, days: i64) {
+        let now = self.test_runner.get_current_time(TimePrecision::Minute);
+        self.round += 1;
+        self.test_runner.advance_to_round_at_timestamp(
+            Round::of(self.round),
+            (now.seconds_since_unix_epoch + days * 86400) * 1000,
+        );
+    }
+
+    // Stakes an amount of the stakable token to a new staking ID, which is deposited to the account along with the stake tokens.
+    fn stake(&mut self, amount: Decimal) {
+        let (account, public_key) = (self.account, self.public_key);
+        self.stake_from(account, public_key, amount, vec![])
+            .expect_commit_success();
+    }
+
+    // Stakes an amount of the stakable token from any account to a new staking ID, signed by the given key, returning the transaction receipt.
+    fn stake_from(
+        &mut self,
+        account: ComponentAddress,
+        public_key: Secp256k1PublicKey,
+        amount: Decimal,
+        access_proofs: Vec<AccessProof>,
+    ) -> TransactionReceiptV1 {
+        let access_proofs = to_manifest_value_and_unwrap!(&access_proofs);
+        let manifest = ManifestBuilder::new()
+            .withdraw_from_account(account, self.stake_address, amount)
+            .take_all_from_worktop(self.stake_address, "stake")
+            .call_method_with_name_lookup(self.component, "stake", |lookup| {
+                (
+                    lookup.bucket("stake"),
+                    None::<ManifestProof>,
+                    access_proofs,
+                    None::<NonFungibleLocalId>,
+                    Some(account),
+                )
+            })
+            .deposit_batch(account)
+            .build();
+        self.test_runner
+            .execute_manifest_ignoring_fee(manifest, vec![NonFungibleGlobalId::from_public_key(&public_key)])
+    }
+}
+
+// Hashes two nodes of a Merkle tree into their parent node, in sorted order like the staking component.
+fn merkle_parent(left: Hash, right: Hash) -> Hash {
+    if left.0 <= right.0 {
+        hash([left.0, right.0].concat())
+    } else {
+        hash([right.0, left.0].concat())
+    }
+}
+
+#[test]
+fn test_stake_token_minted_to_account_and_not_transferable() {
+    let mut setup = RunnerSetup::new();
+    let (_other_public_key, _other_private_key, other_account) = setup.test_runner.new_allocated_account();
+
+    setup.stake(dec!(100));
+    assert_eq!(
+        setup.test_runner.get_component_balance(setup.account, setup.stake_token),
+        dec!(100)
+    );
+
+    // the deposit rule only lets the staking component deposit stake tokens
+    let manifest = ManifestBuilder::new()
+        .withdraw_from_account(setup.account, setup.stake_token, dec!(100))
+        .try_deposit_entire_worktop_or_abort(other_account, None)
         .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    setup.execute(manifest).expect_commit_failure();
+    assert_eq!(
+        setup.test_runner.get_component_balance(other_account, setup.stake_token),
+        dec!(0)
     );
-    println!("{:?}\n", receipt);
-    receipt.expect_commit_success();
 }
 
 #[test]
-fn test_hello_with_test_environment() -> Result<(), RuntimeError> {
-    // Arrange
-    let mut env = TestEnvironment::new();
-    let package_address = Package::compile_and_publish(this_package!(), &mut env)?;
+fn test_emergency_withdraw_burns_stake_tokens() {
+    let mut setup = RunnerSetup::new();
+    setup.stake(dec!(100));
 
-    let mut hello = Hello::instantiate_hello(package_address, &mut env)?;
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_amount(setup.account, setup.controller, dec!(1))
+        .call_method(setup.component, "enable_emergency_exit", manifest_args!())
+        .build();
+    setup.execute(manifest).expect_commit_success();
 
-    // Act
-    let bucket = hello.free_token(&mut env)?;
+    let (account, component, id_address, stake_token) =
+        (setup.account, setup.component, setup.id_address, setup.stake_token);
+    let withdraw = |stake_tokens: Decimal| {
+        ManifestBuilder::new()
+            .create_proof_from_account_of_non_fungibles(
+                account,
+                id_address,
+                [NonFungibleLocalId::integer(1)],
+            )
+            .pop_from_auth_zone("id_proof")
+            .withdraw_from_account(account, stake_token, stake_tokens)
+            .take_all_from_worktop(stake_token, "stake_tokens")
+            .call_method_with_name_lookup(component, "emergency_withdraw", |lookup| {
+                (lookup.proof("id_proof"), vec![lookup.bucket("stake_tokens")])
+            })
+            .deposit_batch(account)
+            .build()
+    };
 
-    // Assert
-    let amount = bucket.amount(&mut env)?;
-    assert_eq!(amount, dec!("1"));
+    setup.execute(withdraw(dec!(50))).expect_commit_failure();
+    setup.execute(withdraw(dec!(100))).expect_commit_success();
 
-    Ok(())
+    assert_eq!(setup.test_runner.get_component_balance(account, stake_token), dec!(0));
+    assert_eq!(
+        setup.test_runner.get_component_balance(account, setup.stake_address),
+        dec!(10_000)
+    );
+}
+
+#[test]
+fn test_staking_ids_not_withdrawable_by_default() {
+    let mut setup = RunnerSetup::new();
+    let (_other_public_key, _other_private_key, other_account) = setup.test_runner.new_allocated_account();
+    setup.stake(dec!(100));
+
+    let manifest = ManifestBuilder::new()
+        .withdraw_non_fungibles_from_account(setup.account, setup.id_address, [NonFungibleLocalId::integer(1)])
+        .try_deposit_entire_worktop_or_abort(other_account, None)
+        .build();
+    setup.execute(manifest).expect_commit_failure();
+    assert_eq!(setup.test_runner.get_component_balance(setup.account, setup.id_address), dec!(1));
+}
+
+#[test]
+fn test_migrate_soulbound_id() {
+    let mut setup = RunnerSetup::with_soulbound_ids(true);
+    let (_other_public_key, _other_private_key, other_account) = setup.test_runner.new_allocated_account();
+    let (account, component, id_address) = (setup.account, setup.component, setup.id_address);
+    let create_id = |deposit_directly: bool| {
+        let builder = ManifestBuilder::new()
+            .call_method(component, "create_id", manifest_args!(None::<NonFungibleLocalId>))
+            .take_all_from_worktop(id_address, "id");
+        if deposit_directly {
+            builder.try_deposit_or_abort(account, None, "id").build()
+        } else {
+            builder
+                .call_method_with_name_lookup(component, "deposit_id", |lookup| (lookup.bucket("id"), account))
+                .build()
+        }
+    };
+
+    // a new soulbound staking ID can only be deposited through the staking component
+    setup.execute(create_id(true)).expect_commit_failure();
+    setup.execute(create_id(false)).expect_commit_success();
+    setup.execute(create_id(false)).expect_commit_success();
+    assert_eq!(setup.test_runner.get_component_balance(account, id_address), dec!(2));
+
+    let manifest = ManifestBuilder::new()
+        .withdraw_non_fungibles_from_account(account, id_address, [NonFungibleLocalId::integer(1)])
+        .try_deposit_entire_worktop_or_abort(other_account, None)
+        .build();
+    setup.execute(manifest).expect_commit_failure();
+
+    let migrate = |id: u64, vault: InternalAddress| {
+        ManifestBuilder::new()
+            .create_proof_from_account_of_non_fungibles(account, id_address, [NonFungibleLocalId::integer(id)])
+            .pop_from_auth_zone("id_proof")
+            .call_method_with_name_lookup(component, "migrate_id", |lookup| {
+                (lookup.proof("id_proof"), other_account, vault)
+            })
+            .build()
+    };
+    let account_vault = InternalAddress::new_or_panic(
+        setup.test_runner.get_component_vaults(account, id_address)[0].0,
+    );
+    setup.execute(migrate(1, account_vault)).expect_commit_success();
+    assert_eq!(setup.test_runner.get_component_balance(account, id_address), dec!(1));
+    assert_eq!(setup.test_runner.get_component_balance(other_account, id_address), dec!(1));
+
+    // a proof of one staking ID can not recall another one
+    let other_vault = InternalAddress::new_or_panic(
+        setup.test_runner.get_component_vaults(other_account, id_address)[0].0,
+    );
+    setup.execute(migrate(2, other_vault)).expect_commit_failure();
+    assert_eq!(setup.test_runner.get_component_balance(other_account, id_address), dec!(1));
+}
+
+#[test]
+fn test_circuit_breakers_restricted_to_guardian_and_owner() {
+    let mut setup = RunnerSetup::new();
+    let (account, component) = (setup.account, setup.component);
+    let (other_public_key, _other_private_key, other_account) = setup.test_runner.new_allocated_account();
+    let pause = (true, false, false, false, false);
+
+    let manifest = ManifestBuilder::new()
+        .call_method(component, "set_pause", manifest_args!(pause))
+        .build();
+    setup.execute(manifest).expect_commit_failure();
+    let manifest = ManifestBuilder::new()
+        .call_method(component, "enable_emergency_exit", manifest_args!())
+        .build();
+    setup.execute(manifest).expect_commit_failure();
+
+    // an account without the guardian badge is rejected as well
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_amount(other_account, XRD, dec!(1))
+        .call_method(component, "enable_emergency_exit", manifest_args!())
+        .build();
+    setup
+        .test_runner
+        .execute_manifest_ignoring_fee(manifest, vec![NonFungibleGlobalId::from_public_key(&other_public_key)])
+        .expect_commit_failure();
+
+    for badge in [setup.guardian, setup.controller] {
+        let manifest = ManifestBuilder::new()
+            .create_proof_from_account_of_amount(account, badge, dec!(1))
+            .call_method(component, "set_pause", manifest_args!(pause))
+            .build();
+        setup.execute(manifest).expect_commit_success();
+    }
+
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_amount(account, setup.guardian, dec!(1))
+        .call_method(component, "enable_emergency_exit", manifest_args!())
+        .build();
+    setup.execute(manifest).expect_commit_success();
+}
+
+#[test]
+fn test_stake_access_by_merkle_allowlist() {
+    let mut setup = RunnerSetup::new();
+    let (account, component, controller, stake_address) =
+        (setup.account, setup.component, setup.controller, setup.stake_address);
+    let (member_key, _member_private_key, member) = setup.test_runner.new_virtual_account();
+    let (outsider_key, _outsider_private_key, outsider) = setup.test_runner.new_virtual_account();
+
+    let manifest = ManifestBuilder::new()
+        .withdraw_from_account(account, stake_address, dec!(200))
+        .take_from_worktop(stake_address, dec!(100), "member_stake")
+        .try_deposit_or_abort(member, None, "member_stake")
+        .try_deposit_entire_worktop_or_abort(outsider, None)
+        .build();
+    setup.execute(manifest).expect_commit_success();
+
+    // an allowlist of the member and one other account
+    let member_leaf = hash(member.as_node_id().0);
+    let other_leaf = hash(account.as_node_id().0);
+    let root = merkle_parent(member_leaf, other_leaf);
+    let access = to_manifest_value_and_unwrap!(&StakeAccess::Allowlist(root));
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_amount(account, controller, dec!(1))
+        .call_method(component, "set_stake_access", manifest_args!(stake_address, access))
+        .build();
+    setup.execute(manifest).expect_commit_success();
+
+    // the outsider is not on the allowlist, and can not use the Merkle proof of the member without its signature
+    setup
+        .stake_from(outsider, outsider_key, dec!(100), vec![AccessProof::Allowlist(outsider, vec![other_leaf])])
+        .expect_commit_failure();
+    setup
+        .stake_from(outsider, outsider_key, dec!(100), vec![AccessProof::Allowlist(member, vec![other_leaf])])
+        .expect_commit_failure();
+    setup
+        .stake_from(member, member_key, dec!(100), vec![AccessProof::Allowlist(member, vec![member_leaf])])
+        .expect_commit_failure();
+
+    setup
+        .stake_from(member, member_key, dec!(100), vec![AccessProof::Allowlist(member, vec![other_leaf])])
+        .expect_commit_success();
+    assert_eq!(setup.test_runner.get_component_balance(member, setup.id_address), dec!(1));
+}
+
+#[test]
+fn test_claim_for_deposits_to_payout_account_until_revoked() {
+    let mut setup = RunnerSetup::new();
+    let (account, component, id_address, reward_token) =
+        (setup.account, setup.component, setup.id_address, setup.reward_token);
+    let (_cold_public_key, _cold_private_key, cold_account) = setup.test_runner.new_allocated_account();
+    setup.stake(dec!(100));
+
+    let claim_for = ManifestBuilder::new()
+        .call_method(component, "claim_for", manifest_args!(NonFungibleLocalId::integer(1)))
+        .build();
+    let set_payout_destination = |payout_destination: Option<PayoutDestination>| {
+        let payout_destination = to_manifest_value_and_unwrap!(&payout_destination);
+        ManifestBuilder::new()
+            .create_proof_from_account_of_non_fungibles(account, id_address, [NonFungibleLocalId::integer(1)])
+            .pop_from_auth_zone("id_proof")
+            .call_method_with_name_lookup(component, "set_payout_destination", |lookup| {
+                (lookup.proof("id_proof"), payout_destination)
+            })
+            .build()
+    };
+
+    // without a payout destination, nobody can claim for the staking ID
+    setup
+        .test_runner
+        .execute_manifest_ignoring_fee(claim_for.clone(), vec![])
+        .expect_commit_failure();
+
+    setup
+        .execute(set_payout_destination(Some(PayoutDestination::Account(cold_account))))
+        .expect_commit_success();
+    setup.advance_days(8);
+    setup
+        .test_runner
+        .execute_manifest_ignoring_fee(claim_for.clone(), vec![])
+        .expect_commit_success();
+    assert_eq!(setup.test_runner.get_component_balance(cold_account, reward_token), dec!(100));
+    assert_eq!(setup.test_runner.get_component_balance(account, reward_token), dec!(0));
+
+    setup.execute(set_payout_destination(None)).expect_commit_success();
+    setup.advance_days(7);
+    setup
+        .test_runner
+        .execute_manifest_ignoring_fee(claim_for, vec![])
+        .expect_commit_failure();
+    assert_eq!(setup.test_runner.get_component_balance(cold_account, reward_token), dec!(100));
+}
+
+#[test]
+fn test_id_display_refreshed_on_state_changes() {
+    let mut setup = RunnerSetup::new();
+    let (account, component, controller, id_address) =
+        (setup.account, setup.component, setup.controller, setup.id_address);
+    let (stake_address, stake_token) = (setup.stake_address, setup.stake_token);
+    let set_id_image = |id_image: IdImage| {
+        let id_image = to_manifest_value_and_unwrap!(&id_image);
+        ManifestBuilder::new()
+            .create_proof_from_account_of_amount(account, controller, dec!(1))
+            .call_method(component, "set_id_image", manifest_args!(id_image))
+            .build()
+    };
+
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_amount(account, controller, dec!(1))
+        .call_method(
+            component,
+            "set_id_tiers",
+            manifest_args!(vec![(dec!(0), "Bronze".to_string()), (dec!(100), "Gold".to_string())]),
+        )
+        .build();
+    setup.execute(manifest).expect_commit_success();
+    setup
+        .execute(set_id_image(IdImage::Template("https://img.test/{tier}/{stake}.png".to_string())))
+        .expect_commit_success();
+
+    setup.stake(dec!(150));
+    let id: Id = setup.test_runner.get_non_fungible_data(id_address, NonFungibleLocalId::integer(1));
+    assert_eq!(id.name, "Test Staking ID #1");
+    assert_eq!(id.description, "Total stake: 150. Tier: Gold. Boost: 1x.");
+    assert_eq!(id.key_image_url, Url::of("https://img.test/Gold/150.png"));
+
+    // switching to SVG images only shows up on the staking ID once it is refreshed
+    setup.execute(set_id_image(IdImage::Svg)).expect_commit_success();
+    let manifest = ManifestBuilder::new()
+        .call_method(component, "refresh_id", manifest_args!(NonFungibleLocalId::integer(1)))
+        .build();
+    setup
+        .test_runner
+        .execute_manifest_ignoring_fee(manifest, vec![])
+        .expect_commit_success();
+    let id: Id = setup.test_runner.get_non_fungible_data(id_address, NonFungibleLocalId::integer(1));
+    assert!(id.key_image_url.as_str().starts_with("data:image/svg+xml;utf8,%3Csvg"));
+    assert!(id.key_image_url.as_str().contains("Total stake: 150"));
+
+    let manifest = ManifestBuilder::new()
+        .create_proof_from_account_of_non_fungibles(account, id_address, [NonFungibleLocalId::integer(1)])
+        .pop_from_auth_zone("id_proof")
+        .withdraw_from_account(account, stake_token, dec!(100))
+        .take_all_from_worktop(stake_token, "stake_tokens")
+        .call_method_with_name_lookup(component, "start_unstake", |lookup| {
+            (
+                lookup.proof("id_proof"),
+                stake_address,
+                dec!(100),
+                false,
+                Some(lookup.bucket("stake_tokens")),
+                None::<Vec<NonFungibleLocalId>>,
+            )
+        })
+        .deposit_batch(account)
+        .build();
+    setup.execute(manifest).expect_commit_success();
+    let id: Id = setup.test_runner.get_non_fungible_data(id_address, NonFungibleLocalId::integer(1));
+    assert_eq!(id.description, "Total stake: 50. Tier: Bronze. Boost: 1x.");
+    assert!(id.key_image_url.as_str().contains("Tier: Bronze"));
 }